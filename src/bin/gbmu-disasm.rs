@@ -0,0 +1,96 @@
+use gbmu::cdl::*;
+use gbmu::disasm::*;
+use gbmu::sym::*;
+use gbmu::utils::*;
+use std::env;
+use std::fs::read;
+use std::path::Path;
+
+const USAGE: &str = "Usage: gbmu-disasm [--cdl <file>] <rom> [bank] [start end]";
+
+fn parse_nb(s: &str) -> usize {
+    let res = if let Some(hex) = s.strip_prefix("0x") {
+        usize::from_str_radix(hex, 16)
+    } else {
+        s.parse::<usize>()
+    };
+
+    res.unwrap_or_else(|_| fatal_err(&format!("Bad number: {}\n{}", s, USAGE), 1))
+}
+
+#[quit::main]
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let cdl_path = match args.iter().position(|a| a == "--cdl") {
+        Some(idx) if idx + 1 < args.len() => {
+            let path = args.remove(idx + 1);
+
+            args.remove(idx);
+            Some(path)
+        }
+        Some(_) => fatal_err(USAGE, 1),
+        None => None,
+    };
+    if args.is_empty() || args.len() == 3 || args.len() > 4 {
+        fatal_err(USAGE, 1);
+    }
+
+    let rom = read(&args[0]).unwrap_or_else(|_| fatal_err("Can't read from rom", 102));
+    let bank_nb = rom.len().div_ceil(0x4000);
+    let banks: Vec<usize> = if args.len() > 1 {
+        let bank = parse_nb(&args[1]);
+
+        if bank >= bank_nb {
+            fatal_err(&format!("Bank out of range (0-{})", bank_nb - 1), 5);
+        }
+        vec![bank]
+    } else {
+        (0..bank_nb).collect()
+    };
+    let cdl = match cdl_path {
+        Some(path) => read(&path).unwrap_or_else(|_| fatal_err("Can't read from cdl", 102)),
+        None => Cdl::path(Path::new(&args[0]))
+            .and_then(|path| read(path).ok())
+            .unwrap_or_default(),
+    };
+    let dis = Disasm::new();
+    let syms = Syms::for_rom(&args[0]).unwrap_or_default();
+
+    for bank in banks {
+        let base: u32 = if bank == 0 { 0x0000 } else { 0x4000 };
+        let (start, end) = if args.len() == 4 {
+            (parse_nb(&args[2]) as u32, parse_nb(&args[3]) as u32)
+        } else {
+            (base, base + 0x4000)
+        };
+
+        if start < base || end > base + 0x4000 || start >= end {
+            fatal_err(
+                &format!(
+                    "Range out of bank {} (0x{:04x}-0x{:04x})",
+                    bank,
+                    base,
+                    base + 0x4000
+                ),
+                6,
+            );
+        }
+        let rd = |a: u16| {
+            rom.get(bank * 0x4000 + (a as usize & 0x3fff))
+                .cloned()
+                .unwrap_or(0xff)
+        };
+        let fl = |a: u16| {
+            cdl.get(bank * 0x4000 + (a as usize & 0x3fff))
+                .cloned()
+                .unwrap_or(0)
+        };
+        let bk = |a: u16| if a < 0x4000 { 0 } else { bank };
+
+        println!("; bank {:02x}", bank);
+        print!(
+            "{}",
+            Disasm::to_str(&dis.until(start as u16, end, &rd, &fl), &bk, &syms)
+        );
+    }
+}
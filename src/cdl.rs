@@ -0,0 +1,116 @@
+use std::cell::Cell;
+use std::fs::{read, write};
+use std::path::Path;
+
+pub const CDL_CODE: u8 = 0x01;
+pub const CDL_OPER: u8 = 0x02;
+pub const CDL_DATA: u8 = 0x04;
+
+pub struct Cdl {
+    path: String,
+    flgs: Vec<Cell<u8>>,
+}
+
+impl Drop for Cdl {
+    fn drop(&mut self) {
+        if !self.path.is_empty() && write(&self.path, self.bytes()).is_err() {
+            println!("Error: Can't write {}", self.path);
+        }
+    }
+}
+
+impl Cdl {
+    pub fn new(sz: usize) -> Cdl {
+        Cdl {
+            path: String::new(),
+            flgs: vec![Cell::new(0); sz],
+        }
+    }
+
+    pub fn path(rom: &Path) -> Option<String> {
+        rom.file_stem()
+            .and_then(|s| s.to_str())
+            .map(|stem| format!("../save/{}.cdl", stem))
+    }
+
+    pub fn for_rom(rom: &Path, sz: usize) -> Cdl {
+        let mut result = Cdl::new(sz);
+
+        if let Some(path) = Cdl::path(rom) {
+            result.path = path;
+            if let Ok(data) = read(&result.path) {
+                result.merge(&data);
+            }
+        }
+        result
+    }
+
+    pub fn merge(&self, data: &[u8]) {
+        for (f, b) in self.flgs.iter().zip(data) {
+            f.set(f.get() | b & (CDL_CODE | CDL_OPER | CDL_DATA));
+        }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.flgs.iter().map(|f| f.get()).collect()
+    }
+
+    pub fn mark(&self, off: usize, flg: u8) {
+        if let Some(f) = self.flgs.get(off) {
+            f.set(f.get() | flg);
+        }
+    }
+
+    pub fn get(&self, off: usize) -> u8 {
+        self.flgs.get(off).map_or(0, |f| f.get())
+    }
+
+    pub fn reset(&self) {
+        self.flgs.iter().for_each(|f| f.set(0));
+    }
+
+    pub fn to_str(&self) -> String {
+        let total = self.flgs.len().max(1);
+        let cnt = |flg: u8| self.flgs.iter().filter(|f| f.get() & flg != 0).count();
+        let line = |nm: &str, n: usize| {
+            format!(
+                "{:<10} {:>8} bytes ({:.2}%)\n",
+                nm,
+                n,
+                n as f64 * 100. / total as f64
+            )
+        };
+
+        [
+            line("Opcodes", cnt(CDL_CODE)),
+            line("Operands", cnt(CDL_OPER)),
+            line("Data", cnt(CDL_DATA)),
+            line(
+                "Untouched",
+                self.flgs.iter().filter(|f| f.get() == 0).count(),
+            ),
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks() {
+        let cdl = Cdl::new(8);
+
+        cdl.mark(0, CDL_CODE);
+        cdl.mark(1, CDL_OPER);
+        cdl.mark(1, CDL_DATA);
+        cdl.mark(9, CDL_CODE);
+        cdl.merge(&[0x00, 0x00, 0x04, 0xf1]);
+        assert_eq!(cdl.bytes(), vec![0x01, 0x06, 0x04, 0x01, 0, 0, 0, 0]);
+        assert_eq!(cdl.get(9), 0);
+        assert!(cdl.to_str().contains("Untouched         4 bytes (50.00%)"));
+        cdl.reset();
+        assert_eq!(cdl.get(1), 0);
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Code {
+    Genie { addr: u16, val: u8, cmp: Option<u8> },
+    Shark { bank: u8, addr: u16, val: u8 },
+}
+
+pub struct Cheat {
+    pub code: String,
+    pub desc: String,
+    pub codes: Vec<Code>,
+    pub on: bool,
+}
+
+pub struct Cheats {
+    pub lst: Vec<Cheat>,
+    genie: bool,
+}
+
+fn digits(code: &str) -> Option<Vec<u8>> {
+    code.chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect()
+}
+
+impl Code {
+    pub fn parse(code: &str) -> Result<Code, String> {
+        let d = digits(code).ok_or_else(|| format!("Bad cheat code: {}", code))?;
+
+        let grps: Vec<usize> = code.split('-').map(|g| g.len()).collect();
+
+        match (d.len(), &grps[..]) {
+            (6, [3, 3]) | (9, [3, 3, 3]) => Ok(Code::Genie {
+                addr: ((d[5] as u16 ^ 0xf) << 12)
+                    | (d[2] as u16) << 8
+                    | (d[3] as u16) << 4
+                    | d[4] as u16,
+                val: d[0] << 4 | d[1],
+                cmp: d.get(8).map(|_| (d[6] << 4 | d[8]).rotate_right(2) ^ 0xba),
+            }),
+            (8, [8]) => Ok(Code::Shark {
+                bank: d[0] << 4 | d[1],
+                addr: (d[6] as u16) << 12 | (d[7] as u16) << 8 | (d[4] as u16) << 4 | d[5] as u16,
+                val: d[2] << 4 | d[3],
+            }),
+            _ => Err(format!("Bad cheat code: {}", code)),
+        }
+    }
+
+    pub fn to_str(&self) -> String {
+        match self {
+            Code::Genie { addr, val, cmp } => format!(
+                "ROM 0x{:04x} = 0x{:02x}{}",
+                addr,
+                val,
+                cmp.map(|c| format!(" if 0x{:02x}", c)).unwrap_or_default()
+            ),
+            Code::Shark { bank, addr, val } => {
+                format!("RAM 0x{:04x} = 0x{:02x} (bank 0x{:02x})", addr, val, bank)
+            }
+        }
+    }
+}
+
+impl Default for Cheats {
+    fn default() -> Cheats {
+        Cheats::new()
+    }
+}
+
+impl Cheats {
+    pub fn new() -> Cheats {
+        Cheats {
+            lst: Vec::new(),
+            genie: false,
+        }
+    }
+
+    pub fn add(&mut self, code: &str, desc: &str, on: bool) -> Result<(), String> {
+        let codes = code
+            .split('+')
+            .map(|c| Code::parse(c.trim()))
+            .collect::<Result<Vec<Code>, String>>()?;
+
+        self.lst.push(Cheat {
+            code: String::from(code),
+            desc: String::from(desc),
+            codes,
+            on,
+        });
+        self.sync();
+        Ok(())
+    }
+
+    pub fn del(&mut self, idx: usize) -> Option<Cheat> {
+        let result = (idx < self.lst.len()).then(|| self.lst.remove(idx));
+
+        self.sync();
+        result
+    }
+
+    pub fn toggle(&mut self, idx: usize, on: bool) -> Option<()> {
+        self.lst.get_mut(idx)?.on = on;
+        self.sync();
+        Some(())
+    }
+
+    fn sync(&mut self) {
+        self.genie = self
+            .lst
+            .iter()
+            .any(|c| c.on && c.codes.iter().any(|c| matches!(c, Code::Genie { .. })));
+    }
+
+    fn on(&self) -> impl Iterator<Item = &Code> {
+        self.lst
+            .iter()
+            .filter(|c| c.on)
+            .flat_map(|c| c.codes.iter())
+    }
+
+    // Like the real adapter, a code matches its address in every ROM bank;
+    // the compare byte is what tells banks apart
+    pub fn patch(&self, addr: u16, old: u8) -> u8 {
+        if !self.genie {
+            return old;
+        }
+        for code in self.on() {
+            if let Code::Genie { addr: a, val, cmp } = code {
+                if *a == addr && cmp.is_none_or(|cmp| cmp == old) {
+                    return *val;
+                }
+            }
+        }
+        old
+    }
+
+    pub fn shark(&self) -> Vec<(u8, u16, u8)> {
+        self.on()
+            .filter_map(|c| match c {
+                Code::Shark { bank, addr, val } => Some((*bank, *addr, *val)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn load(&mut self, path: &str) -> Result<usize, String> {
+        let txt = read_to_string(path).map_err(|_| format!("Can't read {}", path))?;
+        let mut ents: Vec<(String, String, bool)> = Vec::new();
+
+        for line in txt.lines() {
+            let (key, val) = match line.split_once('=') {
+                Some((key, val)) => (key.trim(), val.trim().trim_matches('"')),
+                None => continue,
+            };
+            let (idx, field) = match key.strip_prefix("cheat").and_then(|k| k.split_once('_')) {
+                Some((idx, field)) => match idx.parse::<usize>() {
+                    Ok(idx) => (idx, field),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+
+            if ents.len() <= idx {
+                ents.resize(idx + 1, (String::new(), String::new(), false));
+            }
+            match field {
+                "desc" => ents[idx].0 = String::from(val),
+                "code" => ents[idx].1 = String::from(val),
+                "enable" => ents[idx].2 = val == "true",
+                _ => (),
+            }
+        }
+        let mut result = 0;
+
+        for (desc, code, on) in ents.iter().filter(|e| !e.1.is_empty()) {
+            match self.add(code, desc, *on) {
+                Ok(()) => result += 1,
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn for_rom(rom: &str) -> Cheats {
+        let mut result = Cheats::new();
+        let path = Path::new(rom).with_extension("cht");
+
+        if path.exists() {
+            match result.load(&path.to_string_lossy()) {
+                Ok(n) => println!("{} cheats loaded", n),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        assert_eq!(
+            Code::parse("3E1-23B-FAE"),
+            Ok(Code::Genie {
+                addr: 0x4123,
+                val: 0x3e,
+                cmp: Some(0x05)
+            })
+        );
+        assert_eq!(
+            Code::parse("3e1-23b"),
+            Ok(Code::Genie {
+                addr: 0x4123,
+                val: 0x3e,
+                cmp: None
+            })
+        );
+        assert_eq!(
+            Code::parse("01632AC1"),
+            Ok(Code::Shark {
+                bank: 0x01,
+                addr: 0xc12a,
+                val: 0x63
+            })
+        );
+        assert!(Code::parse("01632AC").is_err());
+        assert!(Code::parse("3E12-3B").is_err());
+        assert!(Code::parse("3E1-23BFAE").is_err());
+        assert!(Code::parse("3E1--23B").is_err());
+        assert!(Code::parse("0163-2AC1").is_err());
+        assert!(Code::parse("3E1-23B-FAZ").is_err());
+    }
+
+    #[test]
+    fn patch() {
+        let mut chts = Cheats::new();
+
+        assert_eq!(chts.patch(0x4123, 0x05), 0x05);
+        chts.add("3E1-23B-FAE", "Lives", true).unwrap();
+        chts.add("01632AC1+80012AA1", "Coins", true).unwrap();
+        assert!(chts.add("bad", "", true).is_err());
+        assert_eq!(chts.patch(0x4123, 0x05), 0x3e);
+        assert_eq!(chts.patch(0x4123, 0x06), 0x06);
+        assert_eq!(
+            chts.shark(),
+            vec![(0x01, 0xc12a, 0x63), (0x80, 0xa12a, 0x01)]
+        );
+        chts.toggle(0, false).unwrap();
+        assert_eq!(chts.patch(0x4123, 0x05), 0x05);
+        assert!(chts.del(2).is_none());
+        assert!(chts.del(1).is_some());
+        assert!(chts.shark().is_empty());
+    }
+
+    #[test]
+    fn load() {
+        let path = std::env::temp_dir().join("gbmu_cheat_test.cht");
+        let mut chts = Cheats::new();
+
+        std::fs::write(
+            &path,
+            "cheats = 2\n\n\
+            cheat0_desc = \"Infinite lives\"\n\
+            cheat0_code = \"3E1-23B-FAE\"\n\
+            cheat0_enable = true\n\n\
+            cheat1_desc = \"Max coins\"\n\
+            cheat1_code = \"01632AC1\"\n\
+            cheat1_enable = false\n",
+        )
+        .unwrap();
+        assert_eq!(chts.load(&path.to_string_lossy()), Ok(2));
+        assert_eq!(chts.lst[0].desc, "Infinite lives");
+        assert!(chts.lst[0].on && !chts.lst[1].on);
+        assert!(chts.shark().is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,1316 @@
+use lazy_regex::regex;
+use regex::Regex;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::ops::Deref;
+
+pub const ADDR_SZ: usize = 0x10000;
+const REGS: [&str; 14] = [
+    "af", "bc", "de", "hl", "sp", "pc", "a", "f", "b", "c", "d", "e", "h", "l",
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cmd {
+    NI,
+    Res,
+    PMRr,
+    PMNn,
+    SFb,
+    SRrNn,
+    SRN,
+    SArrN,
+    SAnnN,
+    BLst,
+    BAdd,
+    BDel,
+    BDlA,
+    BNxt,
+    BNxtN,
+    RShw,
+    Spe,
+    Exit,
+    VRam,
+    Io,
+    Dis,
+    BSym,
+    PMSym,
+    BEn,
+    BDis,
+    Print,
+    WAdd,
+    WLst,
+    WDel,
+    Next,
+    Fin,
+    Until,
+    Bt,
+    EAdd,
+    ELst,
+    EDel,
+    TrcOn,
+    TrcRing,
+    TrcOff,
+    TrcDump,
+    TrcShw,
+    RStep,
+    RCont,
+    Gdb,
+    Src,
+    Alias,
+    ALst,
+    UnAlias,
+    SrchNew,
+    SrchFlt,
+    SrchLst,
+    SrchWtch,
+    SrchCht,
+    ChtLst,
+    ChtAdd,
+    ChtTgl,
+    ChtDel,
+    ChtLoad,
+    MemShw,
+    MemFill,
+    MemCopy,
+    MemFind,
+    MemSave,
+    MemTbl,
+    ProfTgl,
+    ProfShw,
+    ProfSave,
+    CdlShw,
+    CdlRst,
+    TiltShw,
+    TiltSet,
+    Help,
+}
+
+pub struct CmdDef {
+    pub cmd: Cmd,
+    pub nm: &'static str,
+    pub rgx: &'static Regex,
+    pub syn: &'static str,
+    pub help: &'static str,
+    pub ex: &'static str,
+}
+
+fn def(
+    cmd: Cmd,
+    nm: &'static str,
+    rgx: &'static Regex,
+    syn: &'static str,
+    help: &'static str,
+    ex: &'static str,
+) -> CmdDef {
+    CmdDef {
+        cmd,
+        nm,
+        rgx,
+        syn,
+        help,
+        ex,
+    }
+}
+
+pub fn cmds() -> Vec<CmdDef> {
+    vec![
+        def(
+            Cmd::NI,
+            "",
+            regex!(r#"^$"#i),
+            "<enter>",
+            "Execute the next instruction",
+            "",
+        ),
+        def(
+            Cmd::Res,
+            "!",
+            regex!(r#"^!$"#i),
+            "!",
+            "Reset the emulator",
+            "!",
+        ),
+        def(
+            Cmd::PMRr,
+            "dump",
+            regex!(r#"^([[:digit:]]*)\s*\(((?:af)|(?:bc)|(?:de)|(?:hl)|(?:pc)|(?:sp))\)$"#i),
+            "[N] (rr)",
+            "Dump N bytes at the address in a 16-bit register",
+            "16 (hl)",
+        ),
+        def(
+            Cmd::PMNn,
+            "dump",
+            regex!(r#"^([[:digit:]]*)\s*\(0x([[:xdigit:]]{1,4})\)$"#i),
+            "[N] (0xNNNN)",
+            "Dump N bytes at an address",
+            "32 (0xc000)",
+        ),
+        def(
+            Cmd::SFb,
+            "set",
+            regex!(r#"^([znh]|(?:cy))\s*=\s*(true|false)$"#i),
+            "z|n|h|cy = true|false",
+            "Set a flag",
+            "cy = true",
+        ),
+        def(
+            Cmd::SRrNn,
+            "set",
+            regex!(r#"^((?:af)|(?:bc)|(?:de)|(?:hl)|(?:pc)|(?:sp))\s*=\s*0x([[:xdigit:]]{1,4})$"#i),
+            "rr = 0xNNNN",
+            "Set a 16-bit register",
+            "hl = 0xc000",
+        ),
+        def(
+            Cmd::SRN,
+            "set",
+            regex!(r#"^([afbcdehl])\s*=\s*0x([[:xdigit:]]{1,2})$"#i),
+            "r = 0xNN",
+            "Set an 8-bit register",
+            "a = 0x3f",
+        ),
+        def(
+            Cmd::SArrN,
+            "set",
+            regex!(
+                r#"^\(((?:af)|(?:bc)|(?:de)|(?:hl)|(?:pc)|(?:sp))\)\s*=\s*0x([[:xdigit:]]{1,2})$"#i
+            ),
+            "(rr) = 0xNN",
+            "Write a byte at the address in a 16-bit register",
+            "(hl) = 0x00",
+        ),
+        def(
+            Cmd::SAnnN,
+            "set",
+            regex!(r#"^\(0x([[:xdigit:]]{1,4})\)\s*=\s*0x([[:xdigit:]]{1,2})$"#i),
+            "(0xNNNN) = 0xNN",
+            "Write a byte at an address",
+            "(0xc000) = 0x01",
+        ),
+        def(
+            Cmd::BLst,
+            "b",
+            regex!(r#"^b$"#i),
+            "b",
+            "List breakpoints",
+            "b",
+        ),
+        def(
+            Cmd::BAdd,
+            "b",
+            regex!(r#"^b 0x([[:xdigit:]]{1,4})(?:\s+if\s+(.+))?$"#i),
+            "b 0xNNNN [if <expr>]",
+            "Add a breakpoint at an address",
+            "b 0x0150 if a == 0",
+        ),
+        def(
+            Cmd::BDel,
+            "d",
+            regex!(r#"^d ([[:digit:]]+)$"#i),
+            "d N",
+            "Delete a breakpoint",
+            "d 0",
+        ),
+        def(
+            Cmd::BDlA,
+            "da",
+            regex!(r#"^da$"#i),
+            "da",
+            "Delete all breakpoints",
+            "da",
+        ),
+        def(
+            Cmd::BNxt,
+            "n",
+            regex!(r#"^n$"#i),
+            "n",
+            "Run until the next breakpoint",
+            "n",
+        ),
+        def(
+            Cmd::BNxtN,
+            "n",
+            regex!(r#"^n ([[:digit:]]+)$"#i),
+            "n N",
+            "Run, ignoring breakpoints for the next N instructions",
+            "n 3",
+        ),
+        def(
+            Cmd::RShw,
+            "r",
+            regex!(r#"^r$"#i),
+            "r",
+            "Show registers",
+            "r",
+        ),
+        def(
+            Cmd::Spe,
+            "s",
+            regex!(r#"^s$"#i),
+            "s",
+            "Show special registers",
+            "s",
+        ),
+        def(
+            Cmd::Exit,
+            "exit",
+            regex!(r#"^exit$"#i),
+            "exit",
+            "Quit the emulator",
+            "exit",
+        ),
+        def(
+            Cmd::VRam,
+            "vram",
+            regex!(r#"^vram$"#i),
+            "vram",
+            "Open the VRAM viewer",
+            "vram",
+        ),
+        def(
+            Cmd::Io,
+            "io",
+            regex!(r#"^io((?:\s+[[:alpha:]]+)?)$"#i),
+            "io [subsystem]",
+            "Show IO registers",
+            "io lcd",
+        ),
+        def(
+            Cmd::Dis,
+            "dis",
+            regex!(r#"^dis(?:\s+0x([[:xdigit:]]{1,4}))?(?:\s+([[:digit:]]+))?$"#i),
+            "dis [0xNNNN] [N]",
+            "Disassemble N instructions",
+            "dis 0x0150 20",
+        ),
+        def(
+            Cmd::BSym,
+            "b",
+            regex!(r#"^b (_[[:word:].@#$]*|[[:alpha:]](?:[[:alpha:]]{2,}|[[:alpha:]]?[[:digit:]_.@#$])[[:word:].@#$]*)(?:\s+if\s+(.+))?$"#i),
+            "b <sym> [if <expr>]",
+            "Add a breakpoint at a symbol",
+            "b MainLoop",
+        ),
+        def(
+            Cmd::PMSym,
+            "dump",
+            regex!(r#"^([[:digit:]]*)\s*\((_[[:word:].@#$]*|[[:alpha:]](?:[[:alpha:]]{2,}|[[:alpha:]]?[[:digit:]_.@#$])[[:word:].@#$]*)\)$"#i),
+            "[N] (<sym>)",
+            "Dump N bytes at a symbol",
+            "4 (wPlayerX)",
+        ),
+        def(
+            Cmd::BEn,
+            "enable",
+            regex!(r#"^enable ([[:digit:]]+)$"#i),
+            "enable N",
+            "Enable a breakpoint",
+            "enable 0",
+        ),
+        def(
+            Cmd::BDis,
+            "disable",
+            regex!(r#"^disable ([[:digit:]]+)$"#i),
+            "disable N",
+            "Disable a breakpoint",
+            "disable 0",
+        ),
+        def(
+            Cmd::Print,
+            "p|print",
+            regex!(r#"^(?:p|print)\s+(.+)$"#i),
+            "p|print <expr>",
+            "Evaluate an expression, (<expr>) reads the byte at an address and [<expr>] only groups",
+            "p [(hl) + 1] * 2",
+        ),
+        def(
+            Cmd::WAdd,
+            "watch",
+            regex!(
+                r#"^watch (rw|r|w) (?:0x([[:xdigit:]]{1,4})(?:-0x([[:xdigit:]]{1,4}))?|(_[[:word:].@#$]*|[[:alpha:]](?:[[:alpha:]]{2,}|[[:alpha:]]?[[:digit:]_.@#$])[[:word:].@#$]*))(?:\s+if\s+(.+))?$"#i
+            ),
+            "watch r|w|rw 0xNNNN[-0xNNNN]|<sym> [if <expr>]",
+            "Add a watchpoint",
+            "watch w 0xc000-0xc0ff",
+        ),
+        def(
+            Cmd::WLst,
+            "watch",
+            regex!(r#"^watch$"#i),
+            "watch",
+            "List watchpoints",
+            "watch",
+        ),
+        def(
+            Cmd::WDel,
+            "unwatch",
+            regex!(r#"^unwatch ([[:digit:]]+)$"#i),
+            "unwatch N",
+            "Delete a watchpoint",
+            "unwatch 0",
+        ),
+        def(
+            Cmd::Next,
+            "next",
+            regex!(r#"^next$"#i),
+            "next",
+            "Step over calls",
+            "next",
+        ),
+        def(
+            Cmd::Fin,
+            "finish",
+            regex!(r#"^finish$"#i),
+            "finish",
+            "Run until the current function returns",
+            "finish",
+        ),
+        def(
+            Cmd::Until,
+            "until",
+            regex!(r#"^until (?:0x([[:xdigit:]]{1,4})|(_[[:word:].@#$]*|[[:alpha:]](?:[[:alpha:]]{2,}|[[:alpha:]]?[[:digit:]_.@#$])[[:word:].@#$]*))$"#i),
+            "until 0xNNNN|<sym>",
+            "Run until an address is reached",
+            "until 0x0200",
+        ),
+        def(
+            Cmd::Bt,
+            "bt",
+            regex!(r#"^bt$"#i),
+            "bt",
+            "Show the call stack",
+            "bt",
+        ),
+        def(
+            Cmd::EAdd,
+            "break",
+            regex!(r#"^break (int|mode|ly|io|rumble) ([[:alnum:]]+)$"#i),
+            "break int|mode|ly|io|rumble <val>",
+            "Add an event breakpoint",
+            "break int vblank",
+        ),
+        def(
+            Cmd::ELst,
+            "break",
+            regex!(r#"^break$"#i),
+            "break",
+            "List event breakpoints",
+            "break",
+        ),
+        def(
+            Cmd::EDel,
+            "unbreak",
+            regex!(r#"^unbreak ([[:digit:]]+)$"#i),
+            "unbreak N",
+            "Delete an event breakpoint",
+            "unbreak 0",
+        ),
+        def(
+            Cmd::TrcOn,
+            "trace",
+            regex!(r#"^trace on (?P<path>[^[:space:]]+)(?:\s+(doctor|bgb))?$"#i),
+            "trace on <file> [doctor|bgb]",
+            "Trace instructions to a file",
+            "trace on trace.log bgb",
+        ),
+        def(
+            Cmd::TrcRing,
+            "trace",
+            regex!(r#"^trace ring ([[:digit:]]+)(?:\s+(doctor|bgb))?$"#i),
+            "trace ring N [doctor|bgb]",
+            "Trace the last N instructions in memory",
+            "trace ring 10000",
+        ),
+        def(
+            Cmd::TrcOff,
+            "trace",
+            regex!(r#"^trace off$"#i),
+            "trace off",
+            "Stop tracing",
+            "trace off",
+        ),
+        def(
+            Cmd::TrcDump,
+            "trace",
+            regex!(r#"^trace dump (?P<path>[^[:space:]]+)$"#i),
+            "trace dump <file>",
+            "Write the ring buffer to a file",
+            "trace dump ring.log",
+        ),
+        def(
+            Cmd::TrcShw,
+            "trace",
+            regex!(r#"^trace$"#i),
+            "trace",
+            "Show the trace status",
+            "trace",
+        ),
+        def(
+            Cmd::RStep,
+            "rstep",
+            regex!(r#"^rstep$"#i),
+            "rstep",
+            "Step back one instruction",
+            "rstep",
+        ),
+        def(
+            Cmd::RCont,
+            "rcontinue",
+            regex!(r#"^rcontinue$"#i),
+            "rcontinue",
+            "Run back to the previous stop",
+            "rcontinue",
+        ),
+        def(
+            Cmd::Gdb,
+            "gdb",
+            regex!(r#"^gdb(?:\s+([[:digit:]]+))?$"#i),
+            "gdb [port]",
+            "Wait for a GDB client",
+            "gdb 1234",
+        ),
+        def(
+            Cmd::Src,
+            "source",
+            regex!(r#"^source (?P<path>[^[:space:]]+)$"#i),
+            "source <file>",
+            "Run commands from a file",
+            "source setup.gdbmu",
+        ),
+        def(
+            Cmd::Alias,
+            "alias",
+            regex!(r#"^alias ([[:alpha:]_][[:word:]]*)\s+(?P<raw>.+)$"#i),
+            "alias <name> <cmd>",
+            "Define a command alias",
+            "alias bm b MainLoop",
+        ),
+        def(
+            Cmd::ALst,
+            "alias",
+            regex!(r#"^alias$"#i),
+            "alias",
+            "List aliases",
+            "alias",
+        ),
+        def(
+            Cmd::UnAlias,
+            "unalias",
+            regex!(r#"^unalias ([[:alpha:]_][[:word:]]*)$"#i),
+            "unalias <name>",
+            "Delete an alias",
+            "unalias bm",
+        ),
+        def(
+            Cmd::SrchNew,
+            "search",
+            regex!(r#"^search start(?:\s+(8|16))?$"#i),
+            "search start [8|16]",
+            "Start a RAM search over WRAM, HRAM and cartridge RAM",
+            "search start 16",
+        ),
+        def(
+            Cmd::SrchFlt,
+            "search",
+            regex!(r#"^search (eq|ne|gt|lt)(?:\s+(?:0x([[:xdigit:]]{1,4})|([[:digit:]]+)))?$"#i),
+            "search eq|ne|gt|lt [0xNNNN|N]",
+            "Keep values equal, changed, increased or decreased against the last search or a constant",
+            "search lt",
+        ),
+        def(
+            Cmd::SrchLst,
+            "search",
+            regex!(r#"^search$"#i),
+            "search",
+            "List RAM search results",
+            "search",
+        ),
+        def(
+            Cmd::SrchWtch,
+            "search",
+            regex!(r#"^search watch ([[:digit:]]+)(?:\s+(rw|r|w))?$"#i),
+            "search watch N [r|w|rw]",
+            "Add a watchpoint on a search result",
+            "search watch 0 w",
+        ),
+        def(
+            Cmd::SrchCht,
+            "search",
+            regex!(r#"^search cheat ([[:digit:]]+)(?:\s+0x([[:xdigit:]]{1,4}))?$"#i),
+            "search cheat N [0xNNNN]",
+            "Add a GameShark cheat freezing a search result",
+            "search cheat 0 0x63",
+        ),
+        def(
+            Cmd::ChtLst,
+            "cheat",
+            regex!(r#"^cheat$"#i),
+            "cheat",
+            "List cheats",
+            "cheat",
+        ),
+        def(
+            Cmd::ChtAdd,
+            "cheat",
+            regex!(r#"^cheat add ([[:xdigit:]+-]+)(?:\s+(?P<raw>.+))?$"#i),
+            "cheat add <code>[+<code>...] [desc]",
+            "Add a Game Genie (ABC-DEF[-GHI]) or GameShark (ABCDEFGH) cheat",
+            "cheat add 01632AC1 Max coins",
+        ),
+        def(
+            Cmd::ChtTgl,
+            "cheat",
+            regex!(r#"^cheat (on|off) ([[:digit:]]+)$"#i),
+            "cheat on|off N",
+            "Enable or disable a cheat",
+            "cheat off 0",
+        ),
+        def(
+            Cmd::ChtDel,
+            "cheat",
+            regex!(r#"^cheat del ([[:digit:]]+)$"#i),
+            "cheat del N",
+            "Delete a cheat",
+            "cheat del 0",
+        ),
+        def(
+            Cmd::ChtLoad,
+            "cheat",
+            regex!(r#"^cheat load (?P<path>[^[:space:]]+)$"#i),
+            "cheat load <file>",
+            "Load cheats from a .cht file",
+            "cheat load game.cht",
+        ),
+        def(
+            Cmd::MemShw,
+            "mem",
+            regex!(r#"^mem (?:([[:xdigit:]]{1,3}):([[:xdigit:]]{1,4})|0x([[:xdigit:]]{1,4}))(?:\s+([[:digit:]]+))?$"#i),
+            "mem BB:NNNN|0xNNNN [N]",
+            "Show N bytes with a text column, optionally in a given ROM, SRAM, VRAM or WRAM bank",
+            "mem 02:4000 64",
+        ),
+        def(
+            Cmd::MemFill,
+            "mem",
+            regex!(r#"^mem fill (?:([[:xdigit:]]{1,3}):([[:xdigit:]]{1,4})|0x([[:xdigit:]]{1,4})) ([[:digit:]]+) 0x([[:xdigit:]]{1,2})$"#i),
+            "mem fill BB:NNNN|0xNNNN N 0xNN",
+            "Fill N bytes with a value",
+            "mem fill 0xc000 256 0x00",
+        ),
+        def(
+            Cmd::MemCopy,
+            "mem",
+            regex!(r#"^mem copy (?:([[:xdigit:]]{1,3}):([[:xdigit:]]{1,4})|0x([[:xdigit:]]{1,4})) (?:([[:xdigit:]]{1,3}):([[:xdigit:]]{1,4})|0x([[:xdigit:]]{1,4})) ([[:digit:]]+)$"#i),
+            "mem copy BB:NNNN|0xNNNN BB:NNNN|0xNNNN N",
+            "Copy N bytes",
+            "mem copy 01:a000 00:a000 8192",
+        ),
+        def(
+            Cmd::MemFind,
+            "mem",
+            regex!(r#"^mem find (?:([[:xdigit:]]{1,3}):([[:xdigit:]]{1,4})|0x([[:xdigit:]]{1,4})) ([[:digit:]]+) (?P<raw>.+)$"#i),
+            "mem find BB:NNNN|0xNNNN N <bytes>|\"<text>\"",
+            "Search N bytes for hex bytes or text",
+            "mem find 0xc000 8192 \"HERO\"",
+        ),
+        def(
+            Cmd::MemSave,
+            "mem",
+            regex!(r#"^mem save (?:([[:xdigit:]]{1,3}):([[:xdigit:]]{1,4})|0x([[:xdigit:]]{1,4})) ([[:digit:]]+) (?P<path>[^[:space:]]+)$"#i),
+            "mem save BB:NNNN|0xNNNN N <file>",
+            "Write N bytes to a file",
+            "mem save 00:8000 8192 vram.bin",
+        ),
+        def(
+            Cmd::MemTbl,
+            "mem",
+            regex!(r#"^mem tbl (?P<path>[^[:space:]]+)$"#i),
+            "mem tbl <file>|off",
+            "Load a .tbl charmap for the text column",
+            "mem tbl game.tbl",
+        ),
+        def(
+            Cmd::ProfTgl,
+            "prof",
+            regex!(r#"^prof (on|off)$"#i),
+            "prof on|off",
+            "Start (and reset) or stop the cycle profiler",
+            "prof on",
+        ),
+        def(
+            Cmd::ProfShw,
+            "prof",
+            regex!(r#"^prof(?:\s+([[:digit:]]+))?$"#i),
+            "prof [N]",
+            "Show cycles per function, address, interrupt and HALT (top N, default 20)",
+            "prof 10",
+        ),
+        def(
+            Cmd::ProfSave,
+            "prof",
+            regex!(r#"^prof save (?P<path>[^[:space:]]+)$"#i),
+            "prof save <file>",
+            "Save profile as folded stacks for flamegraph.pl",
+            "prof save game.folded",
+        ),
+        def(
+            Cmd::CdlShw,
+            "cdl",
+            regex!(r#"^cdl$"#i),
+            "cdl",
+            "Show ROM coverage (opcodes, operands, data, untouched)",
+            "cdl",
+        ),
+        def(
+            Cmd::CdlRst,
+            "cdl",
+            regex!(r#"^cdl reset$"#i),
+            "cdl reset",
+            "Clear ROM coverage",
+            "cdl reset",
+        ),
+        def(
+            Cmd::TiltShw,
+            "tilt",
+            regex!(r#"^tilt$"#i),
+            "tilt",
+            "Show MBC7 tilt and its input source",
+            "tilt",
+        ),
+        def(
+            Cmd::TiltSet,
+            "tilt",
+            regex!(
+                r#"^tilt (mouse|keys)(?:\s+([[:alnum:]]+)\s+([[:alnum:]]+)\s+([[:alnum:]]+)\s+([[:alnum:]]+))?$"#i
+            ),
+            "tilt mouse|keys [left right up down]",
+            "Drive MBC7 tilt with the mouse in the game window or with keys",
+            "tilt keys a d w s",
+        ),
+        def(
+            Cmd::Help,
+            "help",
+            regex!(r#"^help(?:\s+([^[:space:]]+))?$"#i),
+            "help [cmd]",
+            "Show help",
+            "help trace",
+        ),
+    ]
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Args(pub Vec<String>);
+
+impl Deref for Args {
+    type Target = [String];
+
+    fn deref(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl Args {
+    pub fn addr(&self, idx: usize) -> Result<u16, String> {
+        u16::from_str_radix(&self[idx], 16).map_err(|_| format!("Wrong address: {}", self[idx]))
+    }
+
+    pub fn byte(&self, idx: usize) -> Result<u8, String> {
+        u8::from_str_radix(&self[idx], 16).map_err(|_| format!("Wrong value: {}", self[idx]))
+    }
+
+    pub fn bank(&self, idx: usize) -> Result<usize, String> {
+        usize::from_str_radix(&self[idx], 16).map_err(|_| format!("Wrong bank: {}", self[idx]))
+    }
+
+    pub fn id(&self, idx: usize) -> Result<usize, String> {
+        self[idx]
+            .parse()
+            .map_err(|_| format!("Wrong ID: {}", self[idx]))
+    }
+
+    pub fn cnt(&self, idx: usize, dflt: usize) -> Result<usize, String> {
+        if self[idx].is_empty() {
+            return Ok(dflt);
+        }
+        self[idx]
+            .parse::<usize>()
+            .map(|n| n.min(ADDR_SZ))
+            .map_err(|_| format!("Wrong count: {}", self[idx]))
+    }
+}
+
+fn is_nm(def: &CmdDef, nm: &str) -> bool {
+    def.nm.split('|').any(|n| n == nm)
+}
+
+fn topic(s: &str) -> String {
+    let nm = s.split_whitespace().next().unwrap_or("").to_lowercase();
+
+    if nm.starts_with(|c: char| c == '(' || c.is_ascii_digit()) && !s.contains('=') {
+        String::from("dump")
+    } else if s.contains('=') {
+        String::from("set")
+    } else {
+        nm
+    }
+}
+
+pub fn parse(cmds: &[CmdDef], s: &str) -> Result<(Cmd, Args), String> {
+    for def in cmds {
+        if let Some(c) = def.rgx.captures(s) {
+            let par: Vec<String> = def
+                .rgx
+                .capture_names()
+                .zip(c.iter())
+                .skip(1)
+                .map(|(nm, e)| match (nm, e) {
+                    (Some("path" | "raw"), Some(e)) => String::from(e.as_str()),
+                    (_, Some(e)) => e.as_str().to_lowercase(),
+                    (_, None) => String::new(),
+                })
+                .collect();
+
+            if let Some(nb) = par.iter().find(|p| {
+                !p.is_empty()
+                    && p.chars().all(|c| c.is_ascii_digit())
+                    && p.parse::<usize>().is_err()
+            }) {
+                return Err(format!("Number too large: {}", nb));
+            }
+            return Ok((def.cmd, Args(par)));
+        }
+    }
+    let nm = topic(s);
+    let usage: Vec<&str> = cmds
+        .iter()
+        .filter(|d| is_nm(d, &nm))
+        .map(|d| d.syn)
+        .collect();
+
+    if usage.is_empty() {
+        Err(format!("Unknown command '{}', type 'help' for a list", nm))
+    } else {
+        Err(format!("Wrong syntax, usage: {}", usage.join(" | ")))
+    }
+}
+
+pub fn help(cmds: &[CmdDef], nm: &str) -> Result<String, String> {
+    let mut result = String::new();
+
+    if nm.is_empty() {
+        for def in cmds {
+            result.push_str(&format!("{:<32} {}\n", def.syn, def.help));
+        }
+        result.push_str("Type 'help <cmd>' for details, e.g. 'help b', 'help set', 'help dump'\n");
+        return Ok(result);
+    }
+    for def in cmds.iter().filter(|d| is_nm(d, nm)) {
+        result.push_str(&format!("{}\n    {}\n", def.syn, def.help));
+        if !def.ex.is_empty() {
+            result.push_str(&format!("    Example: {}\n", def.ex));
+        }
+    }
+    if result.is_empty() {
+        Err(format!("No help for '{}'", nm))
+    } else {
+        Ok(result)
+    }
+}
+
+pub struct CmdHelper {
+    nms: Vec<&'static str>,
+}
+
+impl CmdHelper {
+    pub fn new(cmds: &[CmdDef]) -> CmdHelper {
+        let mut nms: Vec<&'static str> = cmds
+            .iter()
+            .filter(|d| !d.nm.is_empty() && d.syn.starts_with(d.nm))
+            .flat_map(|d| d.nm.split('|'))
+            .collect();
+
+        nms.sort_unstable();
+        nms.dedup();
+        CmdHelper { nms }
+    }
+
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || "()=[]+-*/&|^!~<>".contains(c))
+            .map_or(0, |i| i + 1);
+        let word = line[start..pos].to_lowercase();
+        let first = line[..start].trim();
+        let lst: Vec<&str> = if first.is_empty() || first.eq_ignore_ascii_case("help") {
+            self.nms.clone()
+        } else {
+            REGS.to_vec()
+        };
+
+        (
+            start,
+            lst.iter()
+                .filter(|nm| nm.starts_with(&word))
+                .map(|nm| String::from(*nm))
+                .collect(),
+        )
+    }
+}
+
+impl Completer for CmdHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for CmdHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CmdHelper {}
+
+impl Validator for CmdHelper {}
+
+impl Helper for CmdHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex() {
+        let cmds = cmds();
+        let ents = vec![
+            "",
+            "!",
+            "   ",
+            "\t",
+            "100 (0xff)",
+            "(0xff)",
+            "100 (0xfffff)",
+            "af (0xfffff)",
+            "(0xffffff)",
+            "100 (af)",
+            "(hl)",
+            "100 (a)",
+            "af (rn)",
+            "(rn)",
+            "Z =false",
+            "cy= true",
+            "h=true",
+            "r = true",
+            "n=tru",
+            "=(0xdead)",
+            "af =0xdead",
+            "af= 0xdaaad",
+            "bc = 0xd",
+            "de=  0x",
+            "d =0x",
+            "d=   0x00",
+            "e =0x0f",
+            "s =0x0f",
+            "e = 0x0fa",
+            "e = 0x0fdead",
+            "(sp)=0x00",
+            "(af) =0xfffff",
+            "(ac) = 0x10",
+            "(0xff)=  0x10",
+            "(0xfffff) =0x10",
+            "(0x)=0x10",
+            "(0xff) =0xfff",
+            "(0xff) =0x",
+            "b",
+            " b",
+            "b sp",
+            "b x1",
+            "b 0xff",
+            "b 0xfffff",
+            "b 0x",
+            "d 10",
+            "d 0",
+            "d f",
+            "da",
+            "n",
+            "n ",
+            "n 10",
+            "r",
+            "r ",
+            "s",
+            " s",
+            "exit",
+            " exit",
+            "q",
+            "vram",
+            "io",
+            "io lcd",
+            "io  snd",
+            "iolcd",
+            "io 0xff",
+            "dis",
+            "dis 0x150",
+            "dis 0x150 20",
+            "dis 20",
+            "dis 0x",
+            "b MainLoop",
+            "b .local",
+            "(wPlayerX)",
+            "16 (Start.init)",
+            "b 0x1234 if a == 0x10 && (hl) > 3",
+            "b MainLoop if (wPlayerX) != 0",
+            "b 0x1234 if",
+            "enable 2",
+            "disable 0",
+            "disable",
+            "print (hl) + 1",
+            "p a",
+            "print",
+            "watch w 0xc000",
+            "watch rw 0xc000-0xc0ff if a == 1",
+            "watch r wPlayerX",
+            "watch x 0xc000",
+            "watch",
+            "unwatch 1",
+            "next",
+            "finish",
+            "until 0x150",
+            "until MainLoop",
+            "until",
+            "bt",
+            "bt 2",
+            "break int timer",
+            "break io LCDC",
+            "break rumble on",
+            "break ly 0x90",
+            "break mode",
+            "break",
+            "unbreak 0",
+            "trace on Logs/Trace.txt",
+            "trace on trace.log bgb",
+            "trace ring 10000 doctor",
+            "trace ring",
+            "trace off",
+            "trace dump ring.log",
+            "trace",
+            "rstep",
+            "rcontinue",
+            "gdb",
+            "gdb 2345",
+            "gdb port",
+            "source Setup.gdbmu",
+            "source",
+            "alias bm b MainLoop if A == 0",
+            "alias",
+            "unalias BM",
+            "search start",
+            "search start 16",
+            "search gt",
+            "search eq 0x1F",
+            "search ne 300",
+            "search le 3",
+            "search",
+            "search watch 2 rw",
+            "search cheat 0 0x63",
+            "cheat",
+            "cheat add 3E1-23B-FAE+01632AC1 Infinite Lives",
+            "cheat add 01632AC1",
+            "cheat on 1",
+            "cheat off 0",
+            "cheat del 2",
+            "cheat load Game.cht",
+            "cheat add",
+            "mem 02:4000 64",
+            "mem 0xC000",
+            "mem 4000",
+            "mem fill 0xc000 16 0xFF",
+            "mem copy 01:A000 0xc000 8192",
+            "mem find 0:4000 16384 \"HeLLo\"",
+            "mem save 00:8000 8192 Vram.bin",
+            "mem tbl Game.tbl",
+            "prof on",
+            "prof 10",
+            "prof",
+            "prof save Out.folded",
+            "cdl",
+            "cdl reset",
+            "tilt",
+            "tilt mouse",
+            "tilt keys A D kp8 KP2",
+            "tilt keys a d",
+        ];
+        let res = vec![
+            (true, Cmd::NI, vec![]),
+            (true, Cmd::Res, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::PMNn, vec!["100", "ff"]),
+            (true, Cmd::PMNn, vec!["", "ff"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::PMRr, vec!["100", "af"]),
+            (true, Cmd::PMRr, vec!["", "hl"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::SFb, vec!["z", "false"]),
+            (true, Cmd::SFb, vec!["cy", "true"]),
+            (true, Cmd::SFb, vec!["h", "true"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::SRrNn, vec!["af", "dead"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::SRrNn, vec!["bc", "d"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::SRN, vec!["d", "00"]),
+            (true, Cmd::SRN, vec!["e", "0f"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::SArrN, vec!["sp", "00"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::SAnnN, vec!["ff", "10"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::BLst, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::BSym, vec!["x1", ""]),
+            (true, Cmd::BAdd, vec!["ff", ""]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::BDel, vec!["10"]),
+            (true, Cmd::BDel, vec!["0"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::BDlA, vec![]),
+            (true, Cmd::BNxt, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::BNxtN, vec!["10"]),
+            (true, Cmd::RShw, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::Spe, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::Exit, vec![]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::VRam, vec![]),
+            (true, Cmd::Io, vec![""]),
+            (true, Cmd::Io, vec![" lcd"]),
+            (true, Cmd::Io, vec!["  snd"]),
+            (false, Cmd::NI, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::Dis, vec!["", ""]),
+            (true, Cmd::Dis, vec!["150", ""]),
+            (true, Cmd::Dis, vec!["150", "20"]),
+            (true, Cmd::Dis, vec!["", "20"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::BSym, vec!["mainloop", ""]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::PMSym, vec!["", "wplayerx"]),
+            (true, Cmd::PMSym, vec!["16", "start.init"]),
+            (true, Cmd::BAdd, vec!["1234", "a == 0x10 && (hl) > 3"]),
+            (true, Cmd::BSym, vec!["mainloop", "(wplayerx) != 0"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::BEn, vec!["2"]),
+            (true, Cmd::BDis, vec!["0"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::Print, vec!["(hl) + 1"]),
+            (true, Cmd::Print, vec!["a"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::WAdd, vec!["w", "c000", "", "", ""]),
+            (true, Cmd::WAdd, vec!["rw", "c000", "c0ff", "", "a == 1"]),
+            (true, Cmd::WAdd, vec!["r", "", "", "wplayerx", ""]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::WLst, vec![]),
+            (true, Cmd::WDel, vec!["1"]),
+            (true, Cmd::Next, vec![]),
+            (true, Cmd::Fin, vec![]),
+            (true, Cmd::Until, vec!["150", ""]),
+            (true, Cmd::Until, vec!["", "mainloop"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::Bt, vec![]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::EAdd, vec!["int", "timer"]),
+            (true, Cmd::EAdd, vec!["io", "lcdc"]),
+            (true, Cmd::EAdd, vec!["rumble", "on"]),
+            (true, Cmd::EAdd, vec!["ly", "0x90"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::ELst, vec![]),
+            (true, Cmd::EDel, vec!["0"]),
+            (true, Cmd::TrcOn, vec!["Logs/Trace.txt", ""]),
+            (true, Cmd::TrcOn, vec!["trace.log", "bgb"]),
+            (true, Cmd::TrcRing, vec!["10000", "doctor"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::TrcOff, vec![]),
+            (true, Cmd::TrcDump, vec!["ring.log"]),
+            (true, Cmd::TrcShw, vec![]),
+            (true, Cmd::RStep, vec![]),
+            (true, Cmd::RCont, vec![]),
+            (true, Cmd::Gdb, vec![""]),
+            (true, Cmd::Gdb, vec!["2345"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::Src, vec!["Setup.gdbmu"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::Alias, vec!["bm", "b MainLoop if A == 0"]),
+            (true, Cmd::ALst, vec![]),
+            (true, Cmd::UnAlias, vec!["bm"]),
+            (true, Cmd::SrchNew, vec![""]),
+            (true, Cmd::SrchNew, vec!["16"]),
+            (true, Cmd::SrchFlt, vec!["gt", "", ""]),
+            (true, Cmd::SrchFlt, vec!["eq", "1f", ""]),
+            (true, Cmd::SrchFlt, vec!["ne", "", "300"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::SrchLst, vec![]),
+            (true, Cmd::SrchWtch, vec!["2", "rw"]),
+            (true, Cmd::SrchCht, vec!["0", "63"]),
+            (true, Cmd::ChtLst, vec![]),
+            (
+                true,
+                Cmd::ChtAdd,
+                vec!["3e1-23b-fae+01632ac1", "Infinite Lives"],
+            ),
+            (true, Cmd::ChtAdd, vec!["01632ac1", ""]),
+            (true, Cmd::ChtTgl, vec!["on", "1"]),
+            (true, Cmd::ChtTgl, vec!["off", "0"]),
+            (true, Cmd::ChtDel, vec!["2"]),
+            (true, Cmd::ChtLoad, vec!["Game.cht"]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::MemShw, vec!["02", "4000", "", "64"]),
+            (true, Cmd::MemShw, vec!["", "", "c000", ""]),
+            (false, Cmd::NI, vec![]),
+            (true, Cmd::MemFill, vec!["", "", "c000", "16", "ff"]),
+            (
+                true,
+                Cmd::MemCopy,
+                vec!["01", "a000", "", "", "", "c000", "8192"],
+            ),
+            (
+                true,
+                Cmd::MemFind,
+                vec!["0", "4000", "", "16384", "\"HeLLo\""],
+            ),
+            (
+                true,
+                Cmd::MemSave,
+                vec!["00", "8000", "", "8192", "Vram.bin"],
+            ),
+            (true, Cmd::MemTbl, vec!["Game.tbl"]),
+            (true, Cmd::ProfTgl, vec!["on"]),
+            (true, Cmd::ProfShw, vec!["10"]),
+            (true, Cmd::ProfShw, vec![""]),
+            (true, Cmd::ProfSave, vec!["Out.folded"]),
+            (true, Cmd::CdlShw, vec![]),
+            (true, Cmd::CdlRst, vec![]),
+            (true, Cmd::TiltShw, vec![]),
+            (true, Cmd::TiltSet, vec!["mouse", "", "", "", ""]),
+            (true, Cmd::TiltSet, vec!["keys", "a", "d", "kp8", "kp2"]),
+            (false, Cmd::NI, vec![]),
+        ];
+        for (idx, entry) in ents.iter().enumerate() {
+            if let Ok((cmd, par)) = parse(&cmds, entry) {
+                if !res[idx].0 || res[idx].1 != cmd {
+                    panic!("nb:{}", idx);
+                }
+                for (i, p) in par.iter().enumerate() {
+                    assert_eq!(&res[idx].2[i], p);
+                }
+            } else if res[idx].0 {
+                panic!("nb:{}", idx);
+            }
+        }
+    }
+
+    #[test]
+    fn args() {
+        let par = Args(
+            ["c000", "ff", "1g", "99999999", "", "12"]
+                .iter()
+                .map(|s| String::from(*s))
+                .collect(),
+        );
+
+        assert_eq!(par.addr(0), Ok(0xc000));
+        assert_eq!(par.byte(1), Ok(0xff));
+        assert_eq!(par.byte(0), Err(String::from("Wrong value: c000")));
+        assert_eq!(par.bank(2), Err(String::from("Wrong bank: 1g")));
+        assert_eq!(par.cnt(3, 1), Ok(ADDR_SZ));
+        assert_eq!(par.cnt(4, 10), Ok(10));
+        assert_eq!(par.id(5), Ok(12));
+        assert!(par.id(4).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        let cmds = cmds();
+
+        assert_eq!(
+            parse(&cmds, "foo 1"),
+            Err(String::from(
+                "Unknown command 'foo', type 'help' for a list"
+            ))
+        );
+        assert_eq!(
+            parse(&cmds, "unwatch x"),
+            Err(String::from("Wrong syntax, usage: unwatch N"))
+        );
+        assert_eq!(
+            parse(&cmds, "a = 12"),
+            Err(format!(
+                "Wrong syntax, usage: {}",
+                "z|n|h|cy = true|false | rr = 0xNNNN | r = 0xNN | (rr) = 0xNN | (0xNNNN) = 0xNN"
+            ))
+        );
+        assert!(parse(&cmds, "(0xzz)").unwrap_err().contains("(0xNNNN)"));
+        assert_eq!(
+            parse(&cmds, "n 99999999999999999999999"),
+            Err(String::from("Number too large: 99999999999999999999999"))
+        );
+        assert!(help(&cmds, "").unwrap().contains("Show the call stack"));
+        assert!(help(&cmds, "print")
+            .unwrap()
+            .contains("Example: p [(hl) + 1] * 2"));
+        assert!(help(&cmds, "xyz").is_err());
+    }
+
+    #[test]
+    fn complete() {
+        let helper = CmdHelper::new(&cmds());
+
+        assert_eq!(helper.candidates("tr", 2), (0, vec![String::from("trace")]));
+        assert_eq!(
+            helper.candidates("un", 2),
+            (
+                0,
+                vec![
+                    String::from("unalias"),
+                    String::from("unbreak"),
+                    String::from("until"),
+                    String::from("unwatch")
+                ]
+            )
+        );
+        assert_eq!(
+            helper.candidates("help fi", 7),
+            (5, vec![String::from("finish")])
+        );
+        assert_eq!(
+            helper.candidates("p (h", 4),
+            (3, vec![String::from("hl"), String::from("h")])
+        );
+        assert!(helper.nms.contains(&"print") && !helper.nms.contains(&"set"));
+    }
+}
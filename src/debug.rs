@@ -1,48 +1,54 @@
+use crate::cmd::*;
+use crate::disasm::*;
+use crate::disp::*;
+use crate::expr::*;
+use crate::gdb::*;
+use crate::input::*;
+use crate::ioreg::*;
 use crate::mem::*;
+use crate::memed::*;
 use crate::ops::ops::*;
+use crate::prof::*;
 use crate::reg::{api::*, *};
+use crate::search::*;
+use crate::sound::*;
+use crate::sym::*;
+use crate::timer::*;
+use crate::trace::*;
 use crate::utils::*;
-use lazy_regex::regex;
-use minifb::{Key, Window, WindowOptions};
-use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
-use regex::Regex;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{read_to_string, write};
+use std::path::Path;
 use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
 const VRAM_W: usize = 128;
 const VRAM_H: usize = 192;
 const VRAM_UP_CY: usize = 32768;
-
-#[derive(PartialEq, FromPrimitive)]
-enum Cmd {
-    NI,
-    Res,
-    PMRr,
-    PMNn,
-    SFb,
-    SRrNn,
-    SRN,
-    SArrN,
-    SAnnN,
-    BLst,
-    BAdd,
-    BDel,
-    BDlA,
-    BNxt,
-    BNxtN,
-    RShw,
-    Spe,
-    Exit,
-    VRam,
-    Unknown,
-}
+const VRAM_POLL_MS: u64 = 16;
+const SNAP_INT: u64 = 0x20000;
+const SNAP_NB: usize = 64;
+const GDB_PORT: u16 = 1234;
+const GDB_POLL: u64 = 0x400;
+const SRCH_MAX: usize = 32;
+const PROF_MAX: usize = 20;
+const VRAM_PALS: [(&str, Option<u16>); 4] = [
+    ("raw", None),
+    ("BGP", Some(BGP)),
+    ("OBP0", Some(OBP0)),
+    ("OBP1", Some(OBP1)),
+];
 
 pub struct VramDisp {
     buff: Vec<u32>,
     win: Window,
     cycl: usize,
+    pal: usize,
+    click: bool,
 }
 
 impl VramDisp {
@@ -57,6 +63,8 @@ impl VramDisp {
                 }
             },
             cycl: 0,
+            pal: 0,
+            click: false,
         };
         result
             .win
@@ -70,6 +78,7 @@ impl VramDisp {
         if self.cycl == 0 || now {
             let mut b1: u8;
             let mut b2: u8;
+            let pal = VRAM_PALS[self.pal].1.map(|p| m.su_get(p));
 
             for y in 0..24 {
                 for x in 0..16 {
@@ -78,10 +87,14 @@ impl VramDisp {
                         b2 = m.su_get((0x8000 + y * 256 + x * 16 + z * 2 + 1) as u16);
 
                         let mut tmp;
+                        let mut col;
                         for i in 0..8 {
                             tmp = ((i as i8 - 7) * -1) as usize;
-                            self.buff[y * 1024 + z * 128 + x * 8 + i] = COLORS
-                                [(((b1 >> tmp) & 0x1) | (((b2 >> tmp) & 0x1) << 1)) as usize + 1];
+                            col = ((b1 >> tmp) & 0x1) | (((b2 >> tmp) & 0x1) << 1);
+                            if let Some(p) = pal {
+                                col = (p >> (col * 2)) & 0x3;
+                            }
+                            self.buff[y * 1024 + z * 128 + x * 8 + i] = COLORS[col as usize + 1];
                         }
                     }
                 }
@@ -93,69 +106,596 @@ impl VramDisp {
             if !self.win.is_open() {
                 return false;
             }
+            self.handl_evts(m);
         }
         self.cycl -= 1;
         true
     }
+
+    fn poll(&mut self, m: My) -> bool {
+        self.win.update();
+        if !self.win.is_open() {
+            return false;
+        }
+        self.handl_evts(m);
+        self.cycl != 1 || self.update(m, true)
+    }
+
+    fn handl_evts(&mut self, m: My) {
+        if self.win.is_key_pressed(Key::P, KeyRepeat::No) {
+            self.pal = (self.pal + 1) % VRAM_PALS.len();
+            println!("VRAM palette: {}", VRAM_PALS[self.pal].0);
+            self.cycl = 1;
+        }
+        if !self.win.get_mouse_down(MouseButton::Left) {
+            self.click = false;
+        } else if !self.click {
+            self.click = true;
+            if let Some((x, y)) = self.win.get_mouse_pos(MouseMode::Discard) {
+                let (w, h) = self.win.get_size();
+                let (x, y) = (
+                    (x as usize * VRAM_W / w.max(1) / 8).min(15),
+                    (y as usize * VRAM_H / h.max(1) / 8).min(23),
+                );
+
+                VramDisp::tile_info(m, y * 16 + x);
+            }
+        }
+    }
+
+    fn tile_refs(m: My, idx: usize) -> (Vec<(u16, usize, usize)>, Vec<usize>) {
+        let lcdc = m.su_get(LCDC);
+        let mut maps = Vec::new();
+        let mut sprs = Vec::new();
+
+        for map in [0x9800u16, 0x9c00] {
+            for i in 0..1024 {
+                let tile_n = m.su_get(map + i as u16) as usize;
+                let tile = if lcdc & 0x10 == 0 && tile_n < 128 {
+                    tile_n + 256
+                } else {
+                    tile_n
+                };
+                if tile == idx {
+                    maps.push((map, i % 32, i / 32));
+                }
+            }
+        }
+        for i in 0..40 {
+            let tile_n = m.su_get(0xfe00 | (i * 4 + 2) as u16) as usize;
+            if tile_n == idx || (lcdc & 0x4 != 0 && tile_n & 0xfe == idx & 0xfe && idx < 256) {
+                sprs.push(i);
+            }
+        }
+        (maps, sprs)
+    }
+
+    fn tile_info(m: My, idx: usize) {
+        let addr = 0x8000 + idx as u16 * 16;
+        let lcdc = m.su_get(LCDC);
+        let (maps, sprs) = VramDisp::tile_refs(m, idx);
+
+        println!("-------------------------------------------------------");
+        println!("Tile 0x{:03x} (0x{:04x}):", idx, addr);
+        for i in 0..16 {
+            print!("{:02x} ", m.su_get(addr + i));
+        }
+        println!();
+        for map in [0x9800u16, 0x9c00] {
+            let mut usage = Vec::new();
+
+            if (lcdc & 0x8 != 0) == (map == 0x9c00) {
+                usage.push("BG");
+            }
+            if (lcdc & 0x40 != 0) == (map == 0x9c00) {
+                usage.push("window");
+            }
+            print!(
+                "\nMap 0x{:04x}{}:",
+                map,
+                if usage.is_empty() {
+                    String::from("")
+                } else {
+                    format!(" ({})", usage.join(", "))
+                }
+            );
+            let refs: Vec<&(u16, usize, usize)> = maps.iter().filter(|e| e.0 == map).collect();
+            if refs.is_empty() {
+                print!(" None");
+            }
+            for (i, (_, x, y)) in refs.iter().enumerate() {
+                print!("{}({}, {})", if i % 8 == 0 { "\n  " } else { " " }, x, y);
+            }
+        }
+        print!("\n\nSprites:");
+        if sprs.is_empty() {
+            print!(" None");
+        }
+        for s in sprs {
+            print!(" {}", s);
+        }
+        println!("\n-------------------------------------------------------");
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum EBrk {
+    Int(usize),
+    Mode(u8),
+    Ly(u8),
+    Io(u16),
+    Rmbl(bool),
+}
+
+pub struct Snap {
+    pub icnt: u64,
+    pub mem: MemSnap,
+    pub regs: Regs,
+    pub timer: Timer,
+    pub disp: DispSnap,
+    pub audio: AudioSnap,
+    pub halt_bug: bool,
+    pub boot_rom: bool,
+}
+
+enum Flow {
+    Stay,
+    Run,
+    Ret(bool),
+}
+
+type Cond = (String, Expr);
+
+struct Brk {
+    addr: u16,
+    bank: Option<usize>,
+    cond: Option<Cond>,
+    hits: usize,
+    on: bool,
 }
 
 pub struct Debugger {
-    rgxs: Vec<&'static Regex>,
-    edit: Editor<()>,
+    cmds: Vec<CmdDef>,
+    edit: Editor<CmdHelper>,
     debug: bool,
-    brks: Vec<u16>,
+    brks: Vec<Brk>,
+    wtchs: Vec<(Wtch, Option<Cond>, bool)>,
+    ebrks: Vec<EBrk>,
+    ppu: (u8, u8),
+    trc: Trace,
+    icnt: u64,
+    snaps: VecDeque<Snap>,
+    stops: Vec<u64>,
+    replay: Option<u64>,
+    rewind: Option<usize>,
+    pc: u16,
+    tmp: Option<(u16, Option<u16>)>,
+    fin: Option<u16>,
     sbys: bool,
     n_times: usize,
+    dis: Disasm,
+    syms: Syms,
+    gdb: Option<Gdb>,
+    script: VecDeque<String>,
+    batch: bool,
+    alss: HashMap<String, String>,
+    srch: Search,
+    tbl: Option<Tbl>,
+    prof: Prof,
     pub vram: Option<VramDisp>,
 }
 
 impl<'a> Debugger {
     pub fn new(debug: bool) -> Debugger {
-        let result = Debugger {
-            rgxs: vec![
-                regex!(r#"^$"#i),
-                regex!(r#"^!$"#i),
-                regex!(r#"^([[:digit:]]*)\s*\(((?:af)|(?:bc)|(?:de)|(?:hl)|(?:pc)|(?:sp))\)$"#i),
-                regex!(r#"^([[:digit:]]*)\s*\(0x([[:xdigit:]]{1,4})\)$"#i),
-                regex!(r#"^([znh]|(?:cy))\s*=\s*(true|false)$"#i),
-                regex!(r#"^((?:af)|(?:bc)|(?:de)|(?:hl)|(?:pc)|(?:sp))\s*=\s*0x([[:xdigit:]]{1,4})$"#i),
-                regex!(r#"^([afbcdehl])\s*=\s*0x([[:xdigit:]]{1,2})$"#i),
-                regex!(r#"^\(((?:af)|(?:bc)|(?:de)|(?:hl)|(?:pc)|(?:sp))\)\s*=\s*0x([[:xdigit:]]{1,2})$"#i),
-                regex!(r#"^\(0x([[:xdigit:]]{1,4})\)\s*=\s*0x([[:xdigit:]]{1,2})$"#i),
-                regex!(r#"^b$"#i),
-                regex!(r#"^b 0x([[:xdigit:]]{1,4})$"#i),
-                regex!(r#"^d ([[:digit:]]+)$"#i),
-                regex!(r#"^da$"#i),
-                regex!(r#"^n$"#i),
-                regex!(r#"^n ([[:digit:]]+)$"#i),
-                regex!(r#"^r$"#i),
-                regex!(r#"^s$"#i),
-                regex!(r#"^exit$"#i),
-                regex!(r#"^vram$"#i),
-            ],
+        let mut result = Debugger {
+            cmds: cmds(),
             edit: Editor::new(),
             debug,
             brks: Vec::new(),
+            wtchs: Vec::new(),
+            ebrks: Vec::new(),
+            ppu: (0, 0),
+            trc: Trace::new(),
+            icnt: 0,
+            snaps: VecDeque::new(),
+            stops: Vec::new(),
+            replay: None,
+            rewind: None,
+            pc: 0,
+            tmp: None,
+            fin: None,
             n_times: 0,
             sbys: true,
+            dis: Disasm::new(),
+            syms: Syms::new(),
+            gdb: None,
+            script: VecDeque::new(),
+            batch: false,
+            alss: HashMap::new(),
+            srch: Search::new(),
+            tbl: None,
+            prof: Prof::new(),
             vram: None,
         };
+
+        result.edit.set_helper(Some(CmdHelper::new(&result.cmds)));
         result
     }
 
-    fn parse_cmd(&self, s: &'a str) -> Option<(Cmd, Vec<String>)> {
-        for (i, r) in self.rgxs.iter().enumerate() {
-            if let Some(c) = r.captures(s) {
-                return Some((
-                    FromPrimitive::from_usize(i).unwrap(),
-                    c.iter()
-                        .skip(1)
-                        .map(|e| e.unwrap().as_str().to_lowercase())
-                        .collect::<Vec<String>>(),
-                ));
+    pub fn prof(&mut self, m: &Mem, pc: u16, halt: bool, cycles: usize) {
+        if self.prof.on {
+            self.prof.add(m, (Debugger::bank(m, pc), pc), halt, cycles);
+        }
+    }
+
+    pub fn load_syms(&mut self, rom: &str) {
+        if let Some(syms) = Syms::for_rom(rom) {
+            println!("{} symbols loaded", syms.len());
+            self.syms = syms;
+        }
+    }
+
+    pub fn source(&mut self, path: &str) -> Result<usize, String> {
+        let txt = read_to_string(path).map_err(|_| format!("Can't read {}", path))?;
+        let lines: Vec<&str> = txt
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect();
+
+        for l in lines.iter().rev() {
+            self.script.push_front(String::from(*l));
+        }
+        Ok(lines.len())
+    }
+
+    pub fn load_rc(&mut self, rom: &str) {
+        let path = Path::new(rom).with_file_name(".gbmurc");
+
+        if path.exists() {
+            match self.source(&path.to_string_lossy()) {
+                Ok(n) => println!("{} commands loaded from {}", n, path.display()),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+    }
+
+    pub fn batch(&mut self, path: &str) {
+        if let Err(e) = self.source(path) {
+            fatal_err(&e, 1);
+        }
+        self.batch = true;
+    }
+
+    fn alias(&self, entry: String) -> String {
+        let (nm, rest) = entry.split_at(entry.find(char::is_whitespace).unwrap_or(entry.len()));
+
+        match self.alss.get(&nm.to_lowercase()) {
+            Some(cmd) => format!("{}{}", cmd, rest),
+            None => entry,
+        }
+    }
+
+    fn cond(&self, cond: &str) -> Result<Option<Cond>, String> {
+        if cond.is_empty() {
+            Ok(None)
+        } else {
+            Expr::parse(cond, &self.syms).map(|e| Some((String::from(cond), e)))
+        }
+    }
+
+    fn add_brk(&mut self, addr: u16, bank: Option<usize>, cond: &str) {
+        match self.cond(cond) {
+            Ok(cond) => self.brks.push(Brk {
+                addr,
+                bank,
+                cond,
+                hits: 0,
+                on: true,
+            }),
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    fn add_wtch(&mut self, m: &mut Mem, par: &Args) -> Result<(), String> {
+        let (start, end) = if par[3].is_empty() {
+            let start = par.addr(1)?;
+
+            (
+                start,
+                if par[2].is_empty() {
+                    start
+                } else {
+                    par.addr(2)?
+                },
+            )
+        } else {
+            match self.syms.find(&par[3]) {
+                Some((_, addr)) => (addr, addr),
+                None => return Err(String::from("Unknown symbol")),
+            }
+        };
+
+        if end < start {
+            return Err(String::from("Wrong range"));
+        }
+        let cond = self.cond(&par[4])?;
+
+        self.wtchs.push((
+            Wtch {
+                start,
+                end,
+                rd: par[0].contains('r'),
+                wr: par[0].contains('w'),
+            },
+            cond,
+            true,
+        ));
+        self.sync_wtchs(m);
+        Ok(())
+    }
+
+    fn sync_wtchs(&self, m: &mut Mem) {
+        m.wtchs = self
+            .wtchs
+            .iter()
+            .map(|w| w.0)
+            .chain(self.ebrks.iter().filter_map(|e| match e {
+                EBrk::Io(addr) => Some(Wtch {
+                    start: *addr,
+                    end: *addr,
+                    rd: false,
+                    wr: true,
+                }),
+                _ => None,
+            }))
+            .collect();
+    }
+
+    fn add_ebrk(&mut self, m: &mut Mem, kind: &str, val: &str) {
+        let nb = if let Some(hex) = val.strip_prefix("0x") {
+            u16::from_str_radix(hex, 16).ok()
+        } else {
+            val.parse::<u16>().ok()
+        };
+        let ebrk = match kind {
+            "int" => INTS
+                .iter()
+                .position(|i| i.eq_ignore_ascii_case(val))
+                .map(EBrk::Int),
+            "mode" => nb.filter(|n| *n < 4).map(|n| EBrk::Mode(n as u8)),
+            "ly" => nb.filter(|n| *n < 154).map(|n| EBrk::Ly(n as u8)),
+            "rumble" if val == "on" || val == "off" => Some(EBrk::Rmbl(val == "on")),
+            "rumble" => None,
+            _ => io_addr(val).or(nb.filter(|n| *n >= 0xff00)).map(EBrk::Io),
+        };
+
+        match ebrk {
+            Some(ebrk) => {
+                self.ebrks.push(ebrk);
+                self.sync_wtchs(m);
+            }
+            None => println!("Error: Wrong {} value", kind),
+        }
+    }
+
+    fn ebrk_to_str(ebrk: &EBrk) -> String {
+        match ebrk {
+            EBrk::Int(i) => format!("int {}", INTS[*i].to_lowercase()),
+            EBrk::Mode(mode) => format!("mode {}", mode),
+            EBrk::Ly(ly) => format!("ly {}", ly),
+            EBrk::Io(addr) => format!("io {} (0x{:04x})", io_nm(*addr).unwrap_or("?"), addr),
+            EBrk::Rmbl(on) => format!("rumble {}", if *on { "on" } else { "off" }),
+        }
+    }
+
+    fn ebrk_hits(&mut self, m: &mut Mem) -> bool {
+        let mut result = false;
+        let ppu = (m.su_get(STAT) & 0x3, m.su_get(LY));
+
+        if let Some(i) = m.int_evt.take() {
+            if self.ebrks.contains(&EBrk::Int(i)) {
+                println!("Break: {} interrupt dispatched", INTS[i]);
+                result = true;
             }
         }
-        None
+        if let Some(on) = m.rmbl_evt.take() {
+            if self.ebrks.contains(&EBrk::Rmbl(on)) {
+                println!("Break: rumble motor {}", if on { "on" } else { "off" });
+                result = true;
+            }
+        }
+        if ppu.0 != self.ppu.0 && self.ebrks.contains(&EBrk::Mode(ppu.0)) {
+            println!("Break: PPU mode {} (LY={})", ppu.0, ppu.1);
+            result = true;
+        }
+        if ppu.1 != self.ppu.1 && self.ebrks.contains(&EBrk::Ly(ppu.1)) {
+            println!("Break: LY={}", ppu.1);
+            result = true;
+        }
+        self.ppu = ppu;
+        result
+    }
+
+    fn wtch_hits(&mut self, m: &Mem, r: &Regs) -> bool {
+        let mut result = false;
+
+        for hit in m.wtch_hits.take() {
+            let (w, cond, on) = match self.wtchs.get_mut(hit.idx) {
+                Some(w) => w,
+                None => {
+                    println!(
+                        "Break: write {} at PC=0x{:04x}: 0x{:02x} -> 0x{:02x}",
+                        io_nm(hit.addr).unwrap_or("?"),
+                        self.pc,
+                        hit.old,
+                        hit.new.unwrap_or_default()
+                    );
+                    result = true;
+                    continue;
+                }
+            };
+
+            if !*on {
+                continue;
+            }
+            if let Some((_, cond)) = cond {
+                match cond.eval(m, r) {
+                    Ok(0) => continue,
+                    Ok(_) => (),
+                    Err(e) => {
+                        println!("Error: {}, watchpoint {} disabled", e, hit.idx);
+                        *on = false;
+                        continue;
+                    }
+                }
+            }
+            print!(
+                "Watchpoint {}: {} 0x{:04x} at PC=0x{:04x}",
+                hit.idx,
+                if hit.new.is_some() { "write" } else { "read" },
+                hit.addr,
+                self.pc
+            );
+            if let Some(nm) = self.syms.get(Debugger::bank(m, w.start), w.start) {
+                print!(" ({})", nm);
+            }
+            match hit.new {
+                Some(new) => println!(": 0x{:02x} -> 0x{:02x}", hit.old, new),
+                None => println!(": 0x{:02x}", hit.old),
+            }
+            result = true;
+        }
+        m.wtch_hits.borrow_mut().clear();
+        result
+    }
+
+    pub fn snap_due(&self) -> bool {
+        self.debug
+            && self.replay.is_none()
+            && self
+                .snaps
+                .back()
+                .is_none_or(|s| self.icnt >= s.icnt + SNAP_INT)
+    }
+
+    pub fn push_snap(&mut self, m: &mut Mem, mut snap: Snap) {
+        snap.icnt = self.icnt;
+        if self.snaps.len() == SNAP_NB {
+            self.snaps.pop_front();
+            if let Some(s) = self.snaps.front() {
+                let icnt = s.icnt;
+
+                self.stops.retain(|stop| *stop > icnt);
+            }
+        }
+        self.snaps.push_back(snap);
+        if let Some(s) = self.snaps.front() {
+            m.inputs.trim_hist(s.mem.keys_n);
+        }
+        m.inputs.rec = true;
+    }
+
+    pub fn rewind(&mut self) -> Option<&Snap> {
+        let snap = &self.snaps[self.rewind.take()?];
+
+        self.icnt = snap.icnt;
+        Some(snap)
+    }
+
+    fn go_back(&mut self, tgt: Option<u64>) -> bool {
+        match tgt.and_then(|tgt| {
+            self.snaps
+                .iter()
+                .rposition(|s| s.icnt < tgt)
+                .map(|i| (tgt, i))
+        }) {
+            Some((tgt, idx)) => {
+                self.rewind = Some(idx);
+                self.replay = Some(tgt);
+                true
+            }
+            None => {
+                println!("Error: Not enough history");
+                false
+            }
+        }
+    }
+
+    fn diverge(&mut self, m: &mut Mem) {
+        let icnt = self.icnt;
+
+        while self.snaps.back().is_some_and(|s| s.icnt >= icnt) {
+            self.snaps.pop_back();
+        }
+        self.stops.retain(|stop| *stop <= icnt);
+        m.inputs.cut_hist();
+    }
+
+    fn gdb_stop(&mut self, m: &mut Mem, r: &mut Regs, sig: Option<u8>) -> bool {
+        let gdb = match &mut self.gdb {
+            Some(gdb) => gdb,
+            None => return self.get_cmd(m, r),
+        };
+
+        if let Some(sig) = sig {
+            if !gdb.send(&format!("S{:02x}", sig)) {
+                return self.gdb_lost(m, r);
+            }
+        }
+        loop {
+            let act = match self.gdb.as_mut().and_then(|gdb| gdb.handle(m, r)) {
+                Some(act) => act,
+                None => return self.gdb_lost(m, r),
+            };
+
+            m.wtch_hits.borrow_mut().clear();
+            match act {
+                GdbAct::Cont | GdbAct::Step => {
+                    self.sbys = matches!(act, GdbAct::Step);
+                    self.n_times = 0;
+                    return true;
+                }
+                GdbAct::BrkAdd(addr) => self.brks.push(Brk {
+                    addr,
+                    bank: None,
+                    cond: None,
+                    hits: 0,
+                    on: true,
+                }),
+                GdbAct::BrkDel(addr) => {
+                    if let Some(idx) = self
+                        .brks
+                        .iter()
+                        .position(|b| b.addr == addr && b.bank.is_none() && b.cond.is_none())
+                    {
+                        self.brks.remove(idx);
+                    }
+                }
+                GdbAct::Modif => self.diverge(m),
+                GdbAct::Detach => {
+                    println!("GDB detached");
+                    self.gdb = None;
+                    self.sbys = false;
+                    return true;
+                }
+                GdbAct::Kill => quit::with_code(0),
+            }
+        }
+    }
+
+    fn gdb_lost(&mut self, m: &mut Mem, r: &mut Regs) -> bool {
+        println!("GDB connection lost");
+        self.gdb = None;
+        self.get_cmd(m, r)
+    }
+
+    fn trc_fmt(nm: &str) -> TrcFmt {
+        if nm == "bgb" {
+            TrcFmt::Bgb
+        } else {
+            TrcFmt::Doctor
+        }
     }
 
     fn rr_by_nm(r: &'a mut Regs, nm: &str) -> Option<MRR<'a>> {
@@ -194,19 +734,104 @@ impl<'a> Debugger {
         }
     }
 
-    fn mem_dump(m: &Mem, addr: u16, len: usize) {
+    fn bank(m: &Mem, addr: u16) -> usize {
+        if addr < 0x8000 {
+            m.rom_bank(addr)
+        } else {
+            0
+        }
+    }
+
+    fn loc(&self, bank: usize, addr: u16) -> String {
+        match self.syms.get(bank, addr) {
+            Some(nm) => format!("{:02x}:{:04x} ({})", bank, addr, nm),
+            None => format!("{:02x}:{:04x}", bank, addr),
+        }
+    }
+
+    fn backtrace(&self, m: &Mem, r: &Regs) {
+        let sp = grr(&r.sp);
+        let mut pc = (Debugger::bank(m, grr(&r.pc)), grr(&r.pc));
+
         println!("-------------------------------------------------------");
-        for i in 0..len as u16 {
-            if i % 16 == 0 {
-                print!(
-                    "{}0x{:04x}: ",
-                    if i != 0 { "\n" } else { "" },
-                    i.wrapping_add(addr)
-                );
+        for (i, f) in m.calls.iter().rev().enumerate() {
+            print!(
+                "#{:<3} {} in {}",
+                i,
+                self.loc(pc.0, pc.1),
+                self.loc(f.to_bank, f.to)
+            );
+            if f.int {
+                print!(" <interrupt>");
             }
-            print!("{:02x} ", m.nu_get(i.wrapping_add(addr)));
+            if f.sp < sp {
+                print!(" [stale]");
+            }
+            println!();
+            pc = (f.ret_bank, f.ret);
         }
-        println!("\n-------------------------------------------------------");
+        println!("#{:<3} {}", m.calls.len(), self.loc(pc.0, pc.1));
+        if m.calls_bad != 0 || m.calls.last().is_some_and(|f| f.sp < sp) {
+            println!(
+                "Warning: Stack imbalance detected ({} unmatched returns, SP modified directly)",
+                m.calls_bad
+            );
+        }
+        println!("-------------------------------------------------------");
+    }
+
+    fn mem_dump(&self, addr: u16, len: usize, rd: &dyn Fn(u16) -> u8) {
+        let bytes: Vec<Option<u8>> = (0..len.min(ADDR_SZ))
+            .map(|i| Some(rd(addr.wrapping_add(i as u16))))
+            .collect();
+
+        println!("-------------------------------------------------------");
+        print!("{}", dump(addr, &bytes, None, self.tbl.as_ref()));
+        println!("-------------------------------------------------------");
+    }
+
+    fn mem_loc(par: &Args, off: usize) -> Result<(Option<usize>, u16), String> {
+        Ok(if par[off + 2].is_empty() {
+            (Some(par.bank(off)?), par.addr(off + 1)?)
+        } else {
+            (None, par.addr(off + 2)?)
+        })
+    }
+
+    fn mem_rd(m: &Mem, (bank, addr): (Option<usize>, u16), len: usize) -> Vec<Option<u8>> {
+        (addr as usize..(addr as usize + len).min(ADDR_SZ))
+            .map(|a| m.bank_get(bank, a as u16))
+            .collect()
+    }
+
+    fn mem_fmt((bank, addr): (Option<usize>, u16)) -> String {
+        match bank {
+            Some(bank) => format!("{:02x}:{:04x}", bank, addr),
+            None => format!("0x{:04x}", addr),
+        }
+    }
+
+    fn prompt(&mut self, m: &Mem) -> Result<String, ReadlineError> {
+        let vram = match &mut self.vram {
+            Some(vram) => vram,
+            None => return self.edit.readline("> "),
+        };
+        let edit = &mut self.edit;
+
+        let (line, open) = thread::scope(|s| {
+            let line = s.spawn(|| edit.readline("> "));
+            let mut open = true;
+
+            while !line.is_finished() {
+                open = open && vram.poll(m);
+                thread::sleep(Duration::from_millis(VRAM_POLL_MS));
+            }
+            (line.join().unwrap(), open)
+        });
+        if !open {
+            self.vram = None;
+        }
+        line
     }
 
     fn get_cmd(&mut self, m: &mut Mem, r: &mut Regs) -> bool {
@@ -217,121 +842,642 @@ impl<'a> Debugger {
             vram.update(m, true);
         }
         loop {
-            line = self.edit.readline("> ");
-            entry = match line {
-                Ok(s) => s,
-                _ => continue,
-            };
-            if entry.len() != 0 {
-                self.edit.add_history_entry(&entry);
-            }
-            if let Some((cmd, par)) = self.parse_cmd(&entry[..]) {
-                match cmd {
-                    Cmd::NI => break,
-                    Cmd::Res => return false,
-                    Cmd::SFb => {
-                        let tmp = Debugger::f_by_nm(&par[0]).unwrap();
-                        sf((&mut r.af, tmp), bool::from_str(&par[1]).unwrap());
+            if let Some(l) = self.script.pop_front() {
+                println!("> {}", l);
+                entry = l;
+            } else if self.batch {
+                quit::with_code(0);
+            } else {
+                line = self.prompt(m);
+                entry = match line {
+                    Ok(s) => s,
+                    _ => continue,
+                };
+                if entry.len() != 0 {
+                    self.edit.add_history_entry(&entry);
+                }
+            }
+            entry = self.alias(entry);
+            match parse(&self.cmds, &entry).and_then(|(cmd, par)| self.exec(m, r, cmd, &par)) {
+                Ok(Flow::Stay) => (),
+                Ok(Flow::Run) => break,
+                Ok(Flow::Ret(res)) => return res,
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+        true
+    }
+
+    fn exec(&mut self, m: &mut Mem, r: &mut Regs, cmd: Cmd, par: &Args) -> Result<Flow, String> {
+        match cmd {
+            Cmd::NI => return Ok(Flow::Run),
+            Cmd::Res => {
+                self.snaps.clear();
+                self.stops.clear();
+                self.icnt = 0;
+                return Ok(Flow::Ret(false));
+            }
+            Cmd::SFb => {
+                let tmp = Debugger::f_by_nm(&par[0]).ok_or("Unknown flag")?;
+                let val = bool::from_str(&par[1]).map_err(|_| "Wrong value")?;
+
+                self.diverge(m);
+                sf((&mut r.af, tmp), val);
+            }
+            Cmd::PMRr => self.mem_dump(
+                grr(Debugger::rr_by_nm(r, &par[1]).ok_or("Unknown register")?),
+                par.cnt(0, 1)?,
+                &|a| m.su_get(a),
+            ),
+            Cmd::PMNn => self.mem_dump(par.addr(1)?, par.cnt(0, 1)?, &|a| m.su_get(a)),
+            Cmd::PMSym => match self.syms.find(&par[1]) {
+                Some((bank, addr)) => self.mem_dump(addr, par.cnt(0, 1)?, &|a| {
+                    if a < 0x8000 && (a < 0x4000) == (addr < 0x4000) {
+                        m.rom_get(bank, a)
+                    } else {
+                        m.su_get(a)
                     }
-                    Cmd::PMRr => Debugger::mem_dump(
-                        m,
-                        grr(Debugger::rr_by_nm(r, &par[1]).unwrap()),
-                        if par[0].len() == 0 {
-                            1
-                        } else {
-                            usize::from_str_radix(&par[0], 10).unwrap()
-                        },
-                    ),
-                    Cmd::PMNn => Debugger::mem_dump(
-                        m,
-                        u16::from_str_radix(&par[1], 16).unwrap(),
-                        if par[0].len() == 0 {
-                            1
-                        } else {
-                            usize::from_str_radix(&par[0], 10).unwrap()
-                        },
-                    ),
-                    Cmd::SRrNn => {
-                        let tmp1 = Debugger::rr_by_nm(r, &par[0]).unwrap();
-                        let tmp2 = u16::from_str_radix(&par[1], 16).unwrap();
-                        srr(tmp1, tmp2);
+                }),
+                None => println!("Error: Unknown symbol"),
+            },
+            Cmd::SRrNn => {
+                let tmp2 = par.addr(1)?;
+
+                self.diverge(m);
+                srr(
+                    Debugger::rr_by_nm(r, &par[0]).ok_or("Unknown register")?,
+                    tmp2,
+                );
+            }
+            Cmd::SRN => {
+                let tmp2 = par.byte(1)?;
+
+                self.diverge(m);
+                sr(
+                    Debugger::r_by_nm(r, &par[0]).ok_or("Unknown register")?,
+                    tmp2,
+                );
+            }
+            Cmd::SArrN => {
+                let tmp2 = par.byte(1)?;
+                let tmp1 = grr(Debugger::rr_by_nm(r, &par[0]).ok_or("Unknown register")?);
+
+                self.diverge(m);
+                m.su_set(tmp1, tmp2);
+            }
+            Cmd::SAnnN => {
+                let (tmp1, tmp2) = (par.addr(0)?, par.byte(1)?);
+
+                self.diverge(m);
+                m.su_set(tmp1, tmp2);
+            }
+            Cmd::BLst => {
+                println!("-------------------------------------------------------");
+                if self.brks.len() == 0 {
+                    println!("None");
+                }
+                for (idx, brk) in self.brks.iter().enumerate() {
+                    let bank = brk.bank.unwrap_or_else(|| Debugger::bank(m, brk.addr));
+
+                    match brk.bank {
+                        Some(bank) => print!("{}: {:02x}:{:04x}", idx, bank, brk.addr),
+                        None => print!("{}: 0x{:04x}", idx, brk.addr),
                     }
-                    Cmd::SRN => {
-                        let tmp1 = Debugger::r_by_nm(r, &par[0]).unwrap();
-                        let tmp2 = u8::from_str_radix(&par[1], 16).unwrap();
-                        sr(tmp1, tmp2);
+                    if let Some(nm) = self.syms.get(bank, brk.addr) {
+                        print!(" ({})", nm);
                     }
-                    Cmd::SArrN => {
-                        let tmp1 = Debugger::rr_by_nm(r, &par[0]).unwrap();
-                        let tmp2 = u8::from_str_radix(&par[1], 16).unwrap();
-                        m.su_set(grr(tmp1), tmp2);
+                    if let Some((cond, _)) = &brk.cond {
+                        print!(" if {}", cond);
                     }
-                    Cmd::SAnnN => {
-                        let tmp1 = u16::from_str_radix(&par[0], 16).unwrap();
-                        let tmp2 = u8::from_str_radix(&par[1], 16).unwrap();
-                        m.su_set(tmp1, tmp2);
+                    println!(
+                        " [hits: {}]{}",
+                        brk.hits,
+                        if brk.on { "" } else { " [disabled]" }
+                    );
+                }
+                println!("-------------------------------------------------------");
+            }
+            Cmd::BAdd => {
+                let tmp = par.addr(0)?;
+                self.add_brk(tmp, None, &par[1]);
+            }
+            Cmd::BSym => match self.syms.find(&par[0]) {
+                Some((bank, addr)) => self.add_brk(
+                    addr,
+                    if (0x4000..0x8000).contains(&addr) {
+                        Some(bank)
+                    } else {
+                        None
+                    },
+                    &par[1],
+                ),
+                None => println!("Error: Unknown symbol"),
+            },
+            Cmd::BEn | Cmd::BDis => match self.brks.get_mut(par.id(0)?) {
+                Some(brk) => brk.on = cmd == Cmd::BEn,
+                None => println!("Error: Wrong breakpoint ID"),
+            },
+            Cmd::WAdd => self.add_wtch(m, par)?,
+            Cmd::WLst => {
+                println!("-------------------------------------------------------");
+                if self.wtchs.is_empty() {
+                    println!("None");
+                }
+                for (idx, (w, cond, on)) in self.wtchs.iter().enumerate() {
+                    print!(
+                        "{}: {}{} 0x{:04x}",
+                        idx,
+                        if w.rd { "r" } else { "" },
+                        if w.wr { "w" } else { "" },
+                        w.start
+                    );
+                    if w.end != w.start {
+                        print!("-0x{:04x}", w.end);
                     }
-                    Cmd::BLst => {
-                        println!("-------------------------------------------------------");
-                        if self.brks.len() == 0 {
-                            println!("None");
-                        }
-                        for (idx, brk) in self.brks.iter().enumerate() {
-                            println!("{}: 0x{:04x}", idx, brk);
-                        }
-                        println!("-------------------------------------------------------");
+                    if let Some(nm) = self.syms.get(Debugger::bank(m, w.start), w.start) {
+                        print!(" ({})", nm);
                     }
-                    Cmd::BAdd => {
-                        let tmp = u16::from_str_radix(&par[0], 16).unwrap();
-                        self.brks.push(tmp);
+                    if let Some((cond, _)) = cond {
+                        print!(" if {}", cond);
                     }
-                    Cmd::BDel => {
-                        let tmp = usize::from_str_radix(&par[0], 10).unwrap();
-                        if tmp >= self.brks.len() {
-                            println!("Error: Wrong breakpoint ID");
-                        } else {
-                            self.brks.remove(tmp);
+                    println!("{}", if *on { "" } else { " [disabled]" });
+                }
+                println!("-------------------------------------------------------");
+            }
+            Cmd::WDel => {
+                let tmp = par.id(0)?;
+                if tmp >= self.wtchs.len() {
+                    println!("Error: Wrong watchpoint ID");
+                } else {
+                    self.wtchs.remove(tmp);
+                    self.sync_wtchs(m);
+                }
+            }
+            Cmd::Print => match Expr::parse(&par[0], &self.syms).and_then(|e| e.eval(m, r)) {
+                Ok(v) => println!("0x{:04x} ({})", v, v),
+                Err(e) => println!("Error: {}", e),
+            },
+            Cmd::BDel => {
+                let tmp = par.id(0)?;
+                if tmp >= self.brks.len() {
+                    println!("Error: Wrong breakpoint ID");
+                } else {
+                    self.brks.remove(tmp);
+                }
+            }
+            Cmd::BDlA => {
+                self.brks.clear();
+            }
+            Cmd::BNxt => {
+                self.sbys = false;
+                return Ok(Flow::Run);
+            }
+            Cmd::Next => {
+                let pc = grr(&r.pc);
+                let ins = self.dis.ins(pc, &|a| m.su_get(a));
+
+                if ins.txt.starts_with("CALL") || ins.txt.starts_with("RST") {
+                    self.tmp = Some((pc.wrapping_add(ins.bytes.len() as u16), Some(grr(&r.sp))));
+                    self.sbys = false;
+                    self.n_times = 0;
+                }
+                return Ok(Flow::Run);
+            }
+            Cmd::Fin => match m.calls.last() {
+                Some(f) => {
+                    self.fin = Some(f.sp);
+                    self.sbys = false;
+                    self.n_times = 0;
+                    return Ok(Flow::Run);
+                }
+                None => println!("Error: No frame to finish"),
+            },
+            Cmd::Until => {
+                let addr = if par[1].is_empty() {
+                    u16::from_str_radix(&par[0], 16).ok()
+                } else {
+                    self.syms.find(&par[1]).map(|s| s.1)
+                };
+
+                match addr {
+                    Some(addr) => {
+                        self.tmp = Some((addr, None));
+                        self.sbys = false;
+                        self.n_times = 0;
+                        return Ok(Flow::Run);
+                    }
+                    None => println!("Error: Unknown symbol"),
+                }
+            }
+            Cmd::Bt => self.backtrace(m, r),
+            Cmd::TrcOn => {
+                if let Err(e) = self.trc.start_file(&par[0], Debugger::trc_fmt(&par[1])) {
+                    println!("Error: {}", e);
+                }
+            }
+            Cmd::TrcRing => match par[0].parse::<usize>() {
+                Ok(sz) if sz != 0 => self.trc.start_ring(sz, Debugger::trc_fmt(&par[1])),
+                _ => println!("Error: Wrong ring buffer size"),
+            },
+            Cmd::TrcOff => self.trc.stop(),
+            Cmd::TrcDump => match self.trc.dump(&par[0]) {
+                Ok(n) => println!("{} lines written", n),
+                Err(e) => println!("Error: {}", e),
+            },
+            Cmd::RStep => {
+                if self.go_back(self.icnt.checked_sub(1)) {
+                    return Ok(Flow::Run);
+                }
+            }
+            Cmd::RCont => {
+                let tgt = self.stops.iter().rev().find(|s| **s < self.icnt).cloned();
+
+                if self.go_back(tgt) {
+                    return Ok(Flow::Run);
+                }
+            }
+            Cmd::TrcShw => println!("{}", self.trc.status()),
+            Cmd::Src => match self.source(&par[0]) {
+                Ok(n) => println!("{} commands loaded", n),
+                Err(e) => println!("Error: {}", e),
+            },
+            Cmd::Alias => {
+                self.alss.insert(par[0].clone(), par[1].clone());
+            }
+            Cmd::ALst => {
+                println!("-------------------------------------------------------");
+                if self.alss.is_empty() {
+                    println!("None");
+                }
+                let mut alss: Vec<_> = self.alss.iter().collect();
+
+                alss.sort();
+                for (nm, cmd) in alss {
+                    println!("{} = {}", nm, cmd);
+                }
+                println!("-------------------------------------------------------");
+            }
+            Cmd::UnAlias => {
+                if self.alss.remove(&par[0]).is_none() {
+                    println!("Error: Unknown alias");
+                }
+            }
+            Cmd::Gdb => {
+                let port = if par[0].is_empty() {
+                    Ok(GDB_PORT)
+                } else {
+                    par[0].parse::<u16>()
+                };
+
+                match port
+                    .map_err(|_| String::from("Wrong port"))
+                    .and_then(Gdb::listen)
+                {
+                    Ok(gdb) => {
+                        self.gdb = Some(gdb);
+                        return Ok(Flow::Ret(self.gdb_stop(m, r, None)));
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+            }
+            Cmd::EAdd => self.add_ebrk(m, &par[0], &par[1]),
+            Cmd::ELst => {
+                println!("-------------------------------------------------------");
+                if self.ebrks.is_empty() {
+                    println!("None");
+                }
+                for (idx, ebrk) in self.ebrks.iter().enumerate() {
+                    println!("{}: {}", idx, Debugger::ebrk_to_str(ebrk));
+                }
+                println!("-------------------------------------------------------");
+            }
+            Cmd::EDel => {
+                let tmp = par.id(0)?;
+                if tmp >= self.ebrks.len() {
+                    println!("Error: Wrong breakpoint ID");
+                } else {
+                    self.ebrks.remove(tmp);
+                    self.sync_wtchs(m);
+                }
+            }
+            Cmd::BNxtN => {
+                self.sbys = false;
+                self.n_times = par.id(0)?;
+                return Ok(Flow::Run);
+            }
+            Cmd::RShw => {
+                println!("{}", r);
+            }
+            Cmd::Spe => {
+                println!("{}", r.spe_to_str(m));
+            }
+            Cmd::Exit => {
+                quit::with_code(0);
+            }
+            Cmd::VRam => {
+                if let None = self.vram {
+                    self.vram = VramDisp::new(m);
+                } else {
+                    println!("Error: VRAM already displayed");
+                }
+            }
+            Cmd::Io => match io_to_str(m, par[0].trim()) {
+                Some(s) => println!("{}", s),
+                None => println!("Error: Unknown subsystem"),
+            },
+            Cmd::Dis => {
+                let addr = if par[0].is_empty() {
+                    grr(&r.pc)
+                } else {
+                    par.addr(0)?
+                };
+                let cnt = par.cnt(1, 10)?;
+                let lst = self
+                    .dis
+                    .cover(addr, cnt, &|a| m.su_get(a), &|a| m.cdl_get(a));
+
+                println!("-------------------------------------------------------");
+                print!(
+                    "{}",
+                    Disasm::to_str(&lst, &|a| Debugger::bank(m, a), &self.syms)
+                );
+                println!("-------------------------------------------------------");
+            }
+            Cmd::SrchNew => {
+                self.srch.start(m, par[0] == "16");
+                println!("{} candidates", self.srch.len());
+            }
+            Cmd::SrchFlt => {
+                let rhs = if !par[1].is_empty() {
+                    u16::from_str_radix(&par[1], 16).ok()
+                } else if !par[2].is_empty() {
+                    par[2].parse::<u16>().ok()
+                } else {
+                    None
+                };
+
+                if rhs.is_none() && !(par[1].is_empty() && par[2].is_empty()) {
+                    println!("Error: Wrong value");
+                } else if self.srch.is_empty() {
+                    println!("Error: No candidates, use 'search start'");
+                } else {
+                    self.srch
+                        .filter(m, Search::op(&par[0]).ok_or("Unknown operator")?, rhs);
+                    print!("{}", self.srch.to_str(m, SRCH_MAX));
+                }
+            }
+            Cmd::SrchLst => {
+                println!("-------------------------------------------------------");
+                print!("{}", self.srch.to_str(m, SRCH_MAX));
+                println!("-------------------------------------------------------");
+            }
+            Cmd::SrchWtch => match self.srch.get(par.id(0)?) {
+                Some(((_, addr), _)) => {
+                    let end = addr + self.srch.w16 as u16;
+                    let mode = if par[1].is_empty() { "w" } else { &par[1] };
+
+                    self.add_wtch(
+                        m,
+                        &Args(vec![
+                            String::from(mode),
+                            format!("{:x}", addr),
+                            format!("{:x}", end),
+                            String::new(),
+                            String::new(),
+                        ]),
+                    )?;
+                }
+                None => println!("Error: Wrong result ID"),
+            },
+            Cmd::SrchCht => {
+                let val = u16::from_str_radix(&par[1], 16).ok();
+
+                match self.srch.cheat(par.id(0)?, val) {
+                    Some(codes) => {
+                        let code = codes.join("+");
+                        let desc = format!("Search result {}", par[0]);
+
+                        match m.chts.add(&code, &desc, true) {
+                            Ok(()) => println!("{}: {}", m.chts.lst.len() - 1, code),
+                            Err(e) => println!("Error: {}", e),
                         }
                     }
-                    Cmd::BDlA => {
-                        self.brks.clear();
+                    None => println!("Error: Wrong result ID"),
+                }
+            }
+            Cmd::ChtLst => {
+                println!("-------------------------------------------------------");
+                if m.chts.lst.is_empty() {
+                    println!("None");
+                }
+                for (idx, cht) in m.chts.lst.iter().enumerate() {
+                    println!(
+                        "{}: {} {}{}",
+                        idx,
+                        if cht.on { "on " } else { "off" },
+                        cht.code.to_uppercase(),
+                        if cht.desc.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" ({})", cht.desc)
+                        }
+                    );
+                    for code in &cht.codes {
+                        println!("    {}", code.to_str());
                     }
-                    Cmd::BNxt => {
-                        self.sbys = false;
+                }
+                println!("-------------------------------------------------------");
+            }
+            Cmd::ChtAdd => {
+                if let Err(e) = m.chts.add(&par[0], &par[1], true) {
+                    println!("Error: {}", e);
+                }
+            }
+            Cmd::ChtTgl => {
+                if m.chts.toggle(par.id(1)?, par[0] == "on").is_none() {
+                    println!("Error: Wrong cheat ID");
+                }
+            }
+            Cmd::ChtDel => {
+                if m.chts.del(par.id(0)?).is_none() {
+                    println!("Error: Wrong cheat ID");
+                }
+            }
+            Cmd::ChtLoad => match m.chts.load(&par[0]) {
+                Ok(n) => println!("{} cheats loaded", n),
+                Err(e) => println!("Error: {}", e),
+            },
+            Cmd::MemShw => {
+                let loc = Debugger::mem_loc(par, 0)?;
+                let bytes = Debugger::mem_rd(m, loc, par.cnt(3, 64)?);
+
+                if bytes.iter().all(|b| b.is_none()) {
+                    println!("Error: Wrong bank");
+                } else {
+                    println!("-------------------------------------------------------");
+                    print!("{}", dump(loc.1, &bytes, loc.0, self.tbl.as_ref()));
+                    println!("-------------------------------------------------------");
+                }
+            }
+            Cmd::MemFill => {
+                let (bank, addr) = Debugger::mem_loc(par, 0)?;
+                let val = par.byte(4)?;
+                let end = (addr as usize + par.cnt(3, 0)?).min(ADDR_SZ);
+
+                self.diverge(m);
+                for a in addr as usize..end {
+                    if let Err(e) = m.bank_set(bank, a as u16, val) {
+                        println!("Error: {}", e);
                         break;
                     }
-                    Cmd::BNxtN => {
-                        self.sbys = false;
-                        self.n_times = usize::from_str_radix(&par[0], 10).unwrap();
-                        break;
+                }
+            }
+            Cmd::MemCopy => {
+                let src = Debugger::mem_loc(par, 0)?;
+                let (bank, addr) = Debugger::mem_loc(par, 3)?;
+                let bytes: Option<Vec<u8>> = Debugger::mem_rd(m, src, par.cnt(6, 0)?)
+                    .into_iter()
+                    .collect();
+
+                if let Some(bytes) = bytes {
+                    self.diverge(m);
+                    for (i, b) in bytes.iter().enumerate() {
+                        if let Err(e) = m.bank_set(bank, addr.wrapping_add(i as u16), *b) {
+                            println!("Error: {}", e);
+                            break;
+                        }
                     }
-                    Cmd::RShw => {
-                        println!("{}", r);
+                } else {
+                    println!("Error: Wrong source bank");
+                }
+            }
+            Cmd::MemFind => match pat(&par[4], self.tbl.as_ref()) {
+                Ok(pat) => {
+                    let (bank, addr) = Debugger::mem_loc(par, 0)?;
+                    let bytes = Debugger::mem_rd(m, (bank, addr), par.cnt(3, 0)?);
+                    let hits = find(&bytes, &pat);
+
+                    println!("-------------------------------------------------------");
+                    if hits.is_empty() {
+                        println!("None");
                     }
-                    Cmd::Spe => {
-                        println!("{}", r.spe_to_str(m));
+                    for hit in hits.iter().take(SRCH_MAX) {
+                        println!("{}", Debugger::mem_fmt((bank, addr + *hit as u16)));
                     }
-                    Cmd::Exit => {
-                        quit::with_code(0);
+                    if hits.len() > SRCH_MAX {
+                        println!("... ({} matches)", hits.len());
                     }
-                    Cmd::VRam => {
-                        if let None = self.vram {
-                            self.vram = VramDisp::new(m);
-                        } else {
-                            println!("Error: VRAM already displayed");
+                    println!("-------------------------------------------------------");
+                }
+                Err(e) => println!("Error: {}", e),
+            },
+            Cmd::MemSave => {
+                let loc = Debugger::mem_loc(par, 0)?;
+                let bytes: Option<Vec<u8>> = Debugger::mem_rd(m, loc, par.cnt(3, 0)?)
+                    .into_iter()
+                    .collect();
+
+                match bytes {
+                    Some(bytes) => match write(&par[4], &bytes) {
+                        Ok(()) => println!("{} bytes written", bytes.len()),
+                        Err(_) => println!("Error: Can't write {}", par[4]),
+                    },
+                    None => println!("Error: Wrong bank"),
+                }
+            }
+            Cmd::MemTbl => {
+                if par[0].eq_ignore_ascii_case("off") {
+                    self.tbl = None;
+                } else {
+                    match Tbl::load(&par[0]) {
+                        Ok(tbl) => {
+                            println!("{} characters loaded", tbl.len());
+                            self.tbl = Some(tbl);
                         }
+                        Err(e) => println!("Error: {}", e),
                     }
-                    _ => println!("Error: Unknown command"),
                 }
-            } else {
-                println!("Error: Unknown command");
             }
+            Cmd::ProfTgl => {
+                if par[0] == "on" {
+                    self.prof.start(&self.syms);
+                    println!("Profiler started");
+                } else {
+                    self.prof.on = false;
+                    println!("Profiler stopped");
+                }
+            }
+            Cmd::ProfShw => {
+                println!("-------------------------------------------------------");
+                print!(
+                    "{}",
+                    self.prof
+                        .report(&self.syms, par[0].parse().unwrap_or(PROF_MAX))
+                );
+                println!("-------------------------------------------------------");
+            }
+            Cmd::ProfSave => match self.prof.save(&self.syms, &par[0]) {
+                Ok(n) => println!("{} stacks saved to {}", n, par[0]),
+                Err(e) => println!("Error: {}", e),
+            },
+            Cmd::CdlShw => {
+                println!("-------------------------------------------------------");
+                print!("{}", m.cdl.to_str());
+                println!("-------------------------------------------------------");
+            }
+            Cmd::CdlRst => {
+                m.cdl.reset();
+                println!("Coverage cleared");
+            }
+            Cmd::TiltShw => {
+                let src = if m.inputs.tilt_mouse {
+                    String::from("mouse")
+                } else {
+                    let keys: Vec<&str> = m.inputs.tilt_keys.iter().map(|k| key_str(*k)).collect();
+
+                    format!("keys {}", keys.join(" "))
+                };
+
+                println!(
+                    "Tilt: x {} y {} ({})",
+                    m.inputs.tilt.0, m.inputs.tilt.1, src
+                );
+            }
+            Cmd::TiltSet => {
+                if par[0] == "mouse" {
+                    m.inputs.tilt_mouse = true;
+                    println!("Tilt follows the mouse");
+                } else if par[1].is_empty() {
+                    m.inputs.tilt_mouse = false;
+                    println!("Tilt follows the keys");
+                } else {
+                    let keys: Option<Vec<Key>> = par[1..].iter().map(|k| key_nm(k)).collect();
+
+                    match keys {
+                        Some(keys) => {
+                            m.inputs.tilt_keys.copy_from_slice(&keys);
+                            m.inputs.tilt_mouse = false;
+                            println!("Tilt follows the keys");
+                        }
+                        None => println!("Error: Unknown key"),
+                    }
+                }
+            }
+            Cmd::Help => match help(&self.cmds, &par[0]) {
+                Ok(s) => {
+                    println!("-------------------------------------------------------");
+                    print!("{}", s);
+                    println!("-------------------------------------------------------");
+                }
+                Err(e) => println!("Error: {}", e),
+            },
         }
-        true
+        Ok(Flow::Stay)
     }
 
     pub fn run(&mut self, m: &mut Mem, r: &mut Regs, op: &Op, p: u16) -> bool {
         if self.debug {
+            self.icnt += 1;
             if let Some(vram) = &mut self.vram {
                 if !vram.update(m, false) {
                     self.vram = None;
@@ -340,29 +1486,107 @@ impl<'a> Debugger {
             if let Some(keys) = &m.inputs.keys {
                 if keys.contains(&Key::F12) {
                     m.inputs.keys = None;
-                    self.sbys = true;
+                    self.sbys = self.replay.is_none();
                 }
             }
-            if self.sbys || (self.brks.contains(&grr(&r.pc)) && self.n_times == 0) {
+            let int = self.icnt.is_multiple_of(GDB_POLL)
+                && self.replay.is_none()
+                && self.gdb.as_mut().is_some_and(|gdb| gdb.interrupted());
+
+            if int {
                 self.sbys = true;
+            }
+            if let Some(tgt) = self.replay {
+                m.wtch_hits.borrow_mut().clear();
+                m.int_evt = None;
+                m.rmbl_evt = None;
+                self.ppu = (m.su_get(STAT) & 0x3, m.su_get(LY));
+                if self.icnt < tgt {
+                    return true;
+                }
+                self.replay = None;
+                self.sbys = true;
+            }
+            if m.wtchs.len()
+                != self.wtchs.len()
+                    + self
+                        .ebrks
+                        .iter()
+                        .filter(|e| matches!(e, EBrk::Io(_)))
+                        .count()
+            {
+                self.sync_wtchs(m);
+            }
+            let pc = grr(&r.pc);
+            let sp = grr(&r.sp);
+            let mut brk = self.wtch_hits(m, r) | self.ebrk_hits(m);
+
+            if self.trc.is_on() {
+                let ins = Disasm::op_ins(op, pc, p, &|a| m.su_get(a));
+
+                self.trc.log(m, r, &ins, Debugger::bank(m, pc));
+            }
 
-                let fm_par: String;
+            self.pc = pc;
+            if self
+                .fin
+                .is_some_and(|fin| m.calls.last().is_none_or(|f| f.sp > fin))
+                || self
+                    .tmp
+                    .is_some_and(|tmp| tmp.0 == pc && tmp.1.is_none_or(|fsp| sp >= fsp))
+            {
+                brk = true;
+            }
 
-                fm_par = match op.len() {
-                    1 => String::from(""),
-                    2 => format!("0x{:02x}", p),
-                    3 => format!("0x{:04x}", p),
-                    _ => fatal_err("Wrong operation length", 4),
+            for (idx, b) in self.brks.iter_mut().enumerate() {
+                if !b.on || b.addr != pc || b.bank.is_some_and(|bank| bank != Debugger::bank(m, pc))
+                {
+                    continue;
+                }
+                let hit = match &b.cond {
+                    Some((_, cond)) => match cond.eval(m, r) {
+                        Ok(val) => val != 0,
+                        Err(e) => {
+                            println!("Error: {}, breakpoint {} disabled", e, idx);
+                            b.on = false;
+                            false
+                        }
+                    },
+                    None => true,
                 };
+
+                if hit {
+                    b.hits += 1;
+                    brk = true;
+                }
+            }
+
+            let mut result = true;
+
+            if self.sbys || (brk && self.n_times == 0) {
+                self.sbys = true;
+                self.tmp = None;
+                self.fin = None;
+                if brk && self.stops.last() != Some(&self.icnt) {
+                    self.stops.push(self.icnt);
+                }
+
+                let ins = Disasm::op_ins(op, pc, p, &|a| m.su_get(a));
+
                 println!(
-                    "0x{:04x}:  {}",
-                    grr(&r.pc),
-                    format!("{}", op).replace("#", &fm_par)
+                    "{}",
+                    Disasm::to_str(&[ins], &|a| Debugger::bank(m, a), &self.syms).trim_end()
                 );
-                return self.get_cmd(m, r);
+                result = if self.gdb.is_some() {
+                    self.gdb_stop(m, r, Some(if int { 2 } else { 5 }))
+                } else {
+                    self.get_cmd(m, r)
+                };
+                m.wtch_hits.borrow_mut().clear();
             } else if self.n_times > 0 {
                 self.n_times -= 1;
             }
+            return result;
         }
         true
     }
@@ -373,141 +1597,208 @@ mod tests {
     use super::*;
 
     #[test]
-    fn regex() {
-        let dbg = Debugger::new(true);
-        let ents = vec![
-            "",
-            "!",
-            "   ",
-            "\t",
-            "100 (0xff)",
-            "(0xff)",
-            "100 (0xfffff)",
-            "af (0xfffff)",
-            "(0xffffff)",
-            "100 (af)",
-            "(hl)",
-            "100 (a)",
-            "af (rn)",
-            "(rn)",
-            "Z =false",
-            "cy= true",
-            "h=true",
-            "r = true",
-            "n=tru",
-            "=(0xdead)",
-            "af =0xdead",
-            "af= 0xdaaad",
-            "bc = 0xd",
-            "de=  0x",
-            "d =0x",
-            "d=   0x00",
-            "e =0x0f",
-            "s =0x0f",
-            "e = 0x0fa",
-            "e = 0x0fdead",
-            "(sp)=0x00",
-            "(af) =0xfffff",
-            "(ac) = 0x10",
-            "(0xff)=  0x10",
-            "(0xfffff) =0x10",
-            "(0x)=0x10",
-            "(0xff) =0xfff",
-            "(0xff) =0x",
-            "b",
-            " b",
-            "b sp",
-            "b 0xff",
-            "b 0xfffff",
-            "b 0x",
-            "d 10",
-            "d 0",
-            "d f",
-            "da",
-            "n",
-            "n ",
-            "n 10",
-            "r",
-            "r ",
-            "s",
-            " s",
-            "exit",
-            " exit",
-            "q",
-            "vram",
-        ];
-        let res = vec![
-            (true, Cmd::NI, vec![]),
-            (true, Cmd::Res, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::PMNn, vec!["100", "ff"]),
-            (true, Cmd::PMNn, vec!["", "ff"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::PMRr, vec!["100", "af"]),
-            (true, Cmd::PMRr, vec!["", "hl"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::SFb, vec!["z", "false"]),
-            (true, Cmd::SFb, vec!["cy", "true"]),
-            (true, Cmd::SFb, vec!["h", "true"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::SRrNn, vec!["af", "dead"]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::SRrNn, vec!["bc", "d"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::SRN, vec!["d", "00"]),
-            (true, Cmd::SRN, vec!["e", "0f"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::SArrN, vec!["sp", "00"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::SAnnN, vec!["ff", "10"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::BLst, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::BAdd, vec!["ff"]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::BDel, vec!["10"]),
-            (true, Cmd::BDel, vec!["0"]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::BDlA, vec![]),
-            (true, Cmd::BNxt, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::BNxtN, vec!["10"]),
-            (true, Cmd::RShw, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::Spe, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::Exit, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (false, Cmd::Unknown, vec![]),
-            (true, Cmd::VRam, vec![]),
+    fn script() {
+        let mut dbg = Debugger::new(true);
+        let path = std::env::temp_dir().join("gbmu_script_test");
+
+        std::fs::write(&path, "# setup\nb 0x150\n\n  alias x print a  \n").unwrap();
+        assert_eq!(dbg.source(&path.to_string_lossy()), Ok(2));
+        assert_eq!(dbg.script, ["b 0x150", "alias x print a"]);
+        assert!(dbg.source("/nonexistent/gbmurc").is_err());
+        dbg.alss.insert(String::from("x"), String::from("print a"));
+        assert_eq!(dbg.alias(String::from("X + 1")), "print a + 1");
+        assert_eq!(dbg.alias(String::from("xy")), "xy");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tile_refs() {
+        let mut mem = Mem::new("");
+
+        mem.su_set(LCDC, 0x91);
+        mem.su_set(0x9800, 0x01);
+        mem.su_set(0x9821, 0x01);
+        mem.su_set(0x9c00, 0x01);
+        mem.su_set(0xfe06, 0x01);
+        assert_eq!(
+            VramDisp::tile_refs(&mem, 1),
+            (
+                vec![(0x9800, 0, 0), (0x9800, 1, 1), (0x9c00, 0, 0)],
+                vec![1]
+            )
+        );
+        mem.su_set(LCDC, 0x85);
+        mem.su_set(0x9800, 0x81);
+        assert_eq!(
+            VramDisp::tile_refs(&mem, 0x101),
+            (vec![(0x9800, 1, 1), (0x9c00, 0, 0)], vec![])
+        );
+        assert_eq!(
+            VramDisp::tile_refs(&mem, 0).1,
+            (0..40).collect::<Vec<usize>>()
+        );
+    }
+
+    fn cmd(dbg: &mut Debugger, m: &mut Mem, r: &mut Regs, line: &str) {
+        let (cmd, par) = parse(&dbg.cmds, line).unwrap();
+
+        assert!(matches!(dbg.exec(m, r, cmd, &par), Ok(Flow::Stay)));
+    }
+
+    #[test]
+    fn ebrks() {
+        let mut dbg = Debugger::new(true);
+        let mut m = Mem::new("");
+        let mut r = Regs::new();
+
+        for line in [
+            "break int Timer",
+            "break mode 2",
+            "break ly 0x90",
+            "break rumble on",
+            "break io scy",
+            "break io 0xff43",
+            "break ly 154",
+            "break mode x",
+            "break rumble maybe",
+        ] {
+            cmd(&mut dbg, &mut m, &mut r, line);
+        }
+        assert_eq!(
+            dbg.ebrks,
+            [
+                EBrk::Int(2),
+                EBrk::Mode(2),
+                EBrk::Ly(0x90),
+                EBrk::Rmbl(true),
+                EBrk::Io(0xff42),
+                EBrk::Io(0xff43)
+            ]
+        );
+
+        m.int_evt = Some(2);
+        assert!(dbg.ebrk_hits(&mut m));
+        assert!(!dbg.ebrk_hits(&mut m));
+        m.int_evt = Some(0);
+        assert!(!dbg.ebrk_hits(&mut m));
+        m.rmbl_evt = Some(true);
+        assert!(dbg.ebrk_hits(&mut m));
+        assert!(!dbg.ebrk_hits(&mut m));
+        m.rmbl_evt = Some(false);
+        assert!(!dbg.ebrk_hits(&mut m));
+        m.su_set(STAT, 0x02);
+        assert!(dbg.ebrk_hits(&mut m));
+        assert!(!dbg.ebrk_hits(&mut m));
+        m.su_set(LY, 0x90);
+        assert!(dbg.ebrk_hits(&mut m));
+        assert!(!dbg.ebrk_hits(&mut m));
+    }
+
+    #[test]
+    fn io_ebrks() {
+        let mut dbg = Debugger::new(true);
+        let mut m = Mem::new("");
+        let mut r = Regs::new();
+        let hits = |m: &mut Mem, addr: u16| {
+            m.nu_set(addr, 0x01);
+            m.wtch_hits
+                .borrow()
+                .iter()
+                .map(|h| h.idx)
+                .collect::<Vec<usize>>()
+        };
+
+        cmd(&mut dbg, &mut m, &mut r, "watch w 0xc000");
+        cmd(&mut dbg, &mut m, &mut r, "break io scy");
+        cmd(&mut dbg, &mut m, &mut r, "break io scx");
+        assert_eq!(hits(&mut m, 0xff42), [1]);
+        assert!(dbg.wtch_hits(&m, &r));
+        cmd(&mut dbg, &mut m, &mut r, "unwatch 0");
+        assert_eq!(hits(&mut m, 0xc000), []);
+        assert_eq!(hits(&mut m, 0xff43), [1]);
+        assert!(dbg.wtch_hits(&m, &r));
+        cmd(&mut dbg, &mut m, &mut r, "unbreak 0");
+        assert_eq!(hits(&mut m, 0xff42), []);
+        assert_eq!(hits(&mut m, 0xff43), [0]);
+        assert!(dbg.wtch_hits(&m, &r));
+        assert!(!dbg.wtch_hits(&m, &r));
+    }
+
+    #[test]
+    fn bad_conds() {
+        let mut dbg = Debugger::new(true);
+        let mut m = Mem::new("");
+        let mut r = Regs::new();
+        let ops = Ops::new();
+
+        cmd(&mut dbg, &mut m, &mut r, "watch w 0xc000 if 1 / 0");
+        cmd(&mut dbg, &mut m, &mut r, "b 0x0000 if 1 % 0");
+        m.nu_set(0xc000, 0x01);
+        assert!(!dbg.wtch_hits(&m, &r));
+        assert!(!dbg.wtchs[0].2);
+        m.nu_set(0xc000, 0x02);
+        assert!(!dbg.wtch_hits(&m, &r));
+        dbg.sbys = false;
+        assert!(dbg.run(&mut m, &mut r, ops.get((0x00, 0x00)).unwrap(), 0));
+        assert!(!dbg.brks[0].on && dbg.brks[0].hits == 0);
+    }
+
+    fn steps(cmds: &[&str], n: usize) -> Vec<u16> {
+        let mut dbg = Debugger::new(true);
+        let mut m = Mem::new("");
+        let mut r = Regs::new();
+        let ops = Ops::new();
+        let prog = [
+            (0xc000, vec![0xcd, 0x10, 0xc0, 0xff, 0x00, 0x00]),
+            (0xc010, vec![0xcd, 0x20, 0xc0, 0xc9]),
+            (0xc020, vec![0xd9]),
+            (0x0038, vec![0xc9]),
         ];
-        for (idx, entry) in ents.iter().enumerate() {
-            if let Some((cmd, par)) = dbg.parse_cmd(&entry[..]) {
-                if !res[idx].0 || res[idx].1 != cmd {
-                    panic!("nb:{}", idx);
-                }
-                for (i, p) in par.iter().enumerate() {
-                    assert_eq!(&res[idx].2[i], p);
-                }
-            } else if res[idx].0 {
-                panic!("nb:{}", idx);
+        let mut stops = vec![];
+
+        for (addr, bytes) in prog.iter() {
+            for (i, b) in bytes.iter().enumerate() {
+                m.su_set(addr + i as u16, *b);
+            }
+        }
+        srr(&mut r.pc, 0xc000);
+        srr(&mut r.sp, 0xdff0);
+        dbg.script = cmds.iter().map(|c| c.to_string()).collect();
+        for _ in 0..n {
+            let pc = grr(&r.pc);
+            let op = ops.get((m.su_get(pc), m.su_get(pc + 1))).unwrap();
+            let p = m.su_get(pc + 1) as u16 | (m.su_get(pc + 2) as u16) << 8;
+            let left = dbg.script.len();
+
+            dbg.run(&mut m, &mut r, op, p);
+            if dbg.script.len() != left {
+                stops.push(pc);
             }
+            srr(&mut r.pc, pc + op.len() as u16);
+            op.exec(&mut r, &mut m, p);
         }
+        stops
+    }
+
+    #[test]
+    fn next() {
+        assert_eq!(
+            steps(&["next", "next", "next", "next"], 8),
+            [0xc000, 0xc003, 0xc004, 0xc005]
+        );
+    }
+
+    #[test]
+    fn finish() {
+        assert_eq!(
+            steps(&["", "", "finish", "finish", ""], 5),
+            [0xc000, 0xc010, 0xc020, 0xc013, 0xc003]
+        );
+    }
+
+    #[test]
+    fn until() {
+        assert_eq!(steps(&["until 0xc020", ""], 3), [0xc000, 0xc020]);
+        assert_eq!(steps(&["until 0xc013", ""], 4), [0xc000, 0xc013]);
     }
 }
@@ -0,0 +1,298 @@
+use crate::cdl::*;
+use crate::ops::ops::*;
+use crate::reg::api::*;
+use crate::sym::*;
+use std::collections::HashSet;
+
+const DB_MAX: u16 = 3;
+
+pub struct Ins {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub txt: String,
+    pub tgt: Option<u16>,
+    pub ptr: Option<u16>,
+    pub note: Option<String>,
+}
+
+pub struct Disasm(Ops);
+
+impl Default for Disasm {
+    fn default() -> Disasm {
+        Disasm::new()
+    }
+}
+
+impl Disasm {
+    pub fn new() -> Disasm {
+        Disasm(Ops::new())
+    }
+
+    pub fn fmt(op: &Op, addr: u16, p: u16) -> (String, Option<u16>, Option<u16>) {
+        let lbl = format!("{}", op);
+        let mut tgt = None;
+        let mut ptr = None;
+        let par = if !lbl.contains('#') {
+            String::new()
+        } else if lbl.starts_with("JR") {
+            let dst = addr.wrapping_add(2).wrapping_add(p as u8 as i8 as u16);
+
+            tgt = Some(dst);
+            format!("0x{:04x}", dst)
+        } else if lbl.starts_with("LDH") {
+            ptr = Some(0xff00 | p);
+            format!("0xff{:02x}", p as u8)
+        } else if lbl.contains("SP") && op.len() == 2 {
+            let sn = p as u8 as i8;
+
+            if sn < 0 {
+                format!("-0x{:02x}", -(sn as i16))
+            } else {
+                format!("0x{:02x}", sn)
+            }
+        } else if op.len() == 2 {
+            format!("0x{:02x}", p as u8)
+        } else {
+            if lbl.starts_with("JP") || lbl.starts_with("CALL") {
+                tgt = Some(p);
+            } else if lbl.contains("(#)") {
+                ptr = Some(p);
+            }
+            format!("0x{:04x}", p)
+        };
+
+        if lbl.starts_with("RST") {
+            tgt = u16::from_str_radix(&lbl[6..], 16).ok();
+        }
+        (lbl.replace('#', &par).replace("+-", "-"), tgt, ptr)
+    }
+
+    pub fn op_ins(op: &Op, addr: u16, p: u16, rd: &dyn Fn(u16) -> u8) -> Ins {
+        let (txt, tgt, ptr) = Disasm::fmt(op, addr, p);
+
+        Ins {
+            addr,
+            bytes: (0..op.len() as u16)
+                .map(|i| rd(addr.wrapping_add(i)))
+                .collect(),
+            txt,
+            tgt,
+            ptr,
+            note: ptr.and_then(io_nm).map(String::from),
+        }
+    }
+
+    pub fn ins(&self, addr: u16, rd: &dyn Fn(u16) -> u8) -> Ins {
+        let opcode = (rd(addr), rd(addr.wrapping_add(1)));
+
+        match self.0.get(opcode) {
+            Some(op) => {
+                let mut p: u16 = 0;
+
+                for i in 1..op.len() as u16 {
+                    p |= (rd(addr.wrapping_add(i)) as u16) << (8 * (i - 1));
+                }
+                Disasm::op_ins(op, addr, p, rd)
+            }
+            None => Ins {
+                addr,
+                bytes: vec![opcode.0],
+                txt: format!("DB 0x{:02x}", opcode.0),
+                tgt: None,
+                ptr: None,
+                note: None,
+            },
+        }
+    }
+
+    pub fn count(&self, addr: u16, cnt: usize, rd: &dyn Fn(u16) -> u8) -> Vec<Ins> {
+        let mut result: Vec<Ins> = Vec::new();
+        let mut pc = addr;
+
+        for _ in 0..cnt {
+            let ins = self.ins(pc, rd);
+
+            pc = pc.wrapping_add(ins.bytes.len() as u16);
+            result.push(ins);
+        }
+        result
+    }
+
+    fn cover_ins(&self, pc: u16, rd: &dyn Fn(u16) -> u8, cdl: &dyn Fn(u16) -> u8) -> Ins {
+        let data = |a: u16| cdl(a) & (CDL_CODE | CDL_OPER) == 0 && cdl(a) & CDL_DATA != 0;
+
+        if data(pc) {
+            let len = 1
+                + (1..DB_MAX)
+                    .take_while(|i| data(pc.wrapping_add(*i)))
+                    .count() as u16;
+            let bytes: Vec<u8> = (0..len).map(|i| rd(pc.wrapping_add(i))).collect();
+            let txt: Vec<String> = bytes.iter().map(|b| format!("0x{:02x}", b)).collect();
+
+            Ins {
+                addr: pc,
+                bytes,
+                txt: format!("DB {}", txt.join(", ")),
+                tgt: None,
+                ptr: None,
+                note: None,
+            }
+        } else {
+            self.ins(pc, rd)
+        }
+    }
+
+    pub fn cover(
+        &self,
+        addr: u16,
+        cnt: usize,
+        rd: &dyn Fn(u16) -> u8,
+        cdl: &dyn Fn(u16) -> u8,
+    ) -> Vec<Ins> {
+        let mut result: Vec<Ins> = Vec::new();
+        let mut pc = addr;
+
+        for _ in 0..cnt {
+            let ins = self.cover_ins(pc, rd, cdl);
+
+            pc = pc.wrapping_add(ins.bytes.len() as u16);
+            result.push(ins);
+        }
+        result
+    }
+
+    pub fn until(
+        &self,
+        addr: u16,
+        end: u32,
+        rd: &dyn Fn(u16) -> u8,
+        cdl: &dyn Fn(u16) -> u8,
+    ) -> Vec<Ins> {
+        let mut result: Vec<Ins> = Vec::new();
+        let mut pc = addr as u32;
+
+        while pc < end {
+            let ins = self.cover_ins(pc as u16, rd, cdl);
+
+            pc += ins.bytes.len() as u32;
+            result.push(ins);
+        }
+        result
+    }
+
+    pub fn line(ins: &Ins, bank: usize) -> String {
+        let bytes: Vec<String> = ins.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let mut result = format!(
+            "{:02x}:{:04x}  {:<9} {}",
+            bank,
+            ins.addr,
+            bytes.join(" "),
+            ins.txt
+        );
+
+        if let Some(note) = &ins.note {
+            result = format!("{:<44}; {}", result, note);
+        }
+        result
+    }
+
+    pub fn to_str(lst: &[Ins], bank: &dyn Fn(u16) -> usize, syms: &Syms) -> String {
+        let addrs: HashSet<u16> = lst.iter().map(|i| i.addr).collect();
+        let tgts: HashSet<u16> = lst
+            .iter()
+            .filter_map(|i| i.tgt)
+            .filter(|t| addrs.contains(t))
+            .collect();
+        let lbl = |a: u16| -> Option<String> {
+            if let Some(nm) = syms.get(bank(a), a) {
+                Some(String::from(nm))
+            } else if tgts.contains(&a) {
+                Some(format!("L_{:04x}", a))
+            } else {
+                None
+            }
+        };
+        let mut result = String::new();
+
+        for ins in lst {
+            if let Some(nm) = lbl(ins.addr) {
+                result.push_str(&format!("{}:\n", nm));
+            }
+            let mut line = Disasm::line(ins, bank(ins.addr));
+
+            if let Some(tgt) = ins.tgt {
+                line = format!(
+                    "{:<44}; -> {}",
+                    line,
+                    if let Some(nm) = lbl(tgt) {
+                        nm
+                    } else if tgt < 0x8000 {
+                        format!("{:02x}:{:04x}", bank(tgt), tgt)
+                    } else {
+                        format!("0x{:04x}", tgt)
+                    }
+                );
+            } else if let Some(nm) = ins.ptr.filter(|_| ins.note.is_none()).and_then(&lbl) {
+                line = format!("{:<44}; {}", line, nm);
+            }
+            result.push_str(&line);
+            result.push('\n');
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operands() {
+        let dis = Disasm::new();
+        let rom: Vec<u8> = vec![
+            0x18, 0xfe, 0xe0, 0x40, 0xf8, 0xfd, 0xcd, 0x34, 0x12, 0xfa, 0x44, 0xff, 0xcb, 0x7c,
+            0xd3, 0xff,
+        ];
+        let rd = |a: u16| rom.get(a as usize).cloned().unwrap_or(0);
+        let lst = dis.until(0, rom.len() as u32, &rd, &|_| 0);
+        let txts: Vec<&str> = lst.iter().map(|i| &i.txt[..]).collect();
+
+        assert_eq!(
+            txts,
+            vec![
+                "JR 0x0000",
+                "LDH (0xff40), A",
+                "LD HL, SP-0x03",
+                "CALL 0x1234",
+                "LD A, (0xff44)",
+                "BIT 7, H",
+                "DB 0xd3",
+                "RST 0x38",
+            ]
+        );
+        assert_eq!(lst[0].tgt, Some(0));
+        assert_eq!(lst[1].note, Some(String::from("LCDC")));
+        assert_eq!(lst[4].note, Some(String::from("LY")));
+        assert_eq!(lst[7].tgt, Some(0x38));
+        assert_eq!(Disasm::line(&lst[3], 1), "01:0006  cd 34 12  CALL 0x1234");
+        assert!(Disasm::to_str(&lst, &|_| 0, &Syms::new()).starts_with("L_0000:\n"));
+        assert!(Disasm::to_str(&lst, &|_| 0, &Syms::parse("00:1234 Func"))
+            .contains("CALL 0x1234              ; -> Func"));
+
+        let cdl = [
+            CDL_CODE,
+            CDL_OPER,
+            CDL_DATA,
+            CDL_DATA | CDL_OPER,
+            CDL_DATA,
+            CDL_DATA,
+        ];
+        let lst = dis.cover(0, 4, &rd, &|a| cdl.get(a as usize).cloned().unwrap_or(0));
+        let txts: Vec<&str> = lst.iter().map(|i| &i.txt[..]).collect();
+
+        assert_eq!(
+            txts,
+            vec!["JR 0x0000", "DB 0xe0", "LD B, B", "DB 0xf8, 0xfd"]
+        );
+    }
+}
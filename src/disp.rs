@@ -9,6 +9,10 @@ const LCD_W: usize = 160;
 const LCD_H: usize = 144;
 
 const ZOOM: usize = 6;
+const SHAKE: usize = 2;
+
+const TITLE: &str = "Falco's GBMU";
+const TITLE_RMBL: &str = "Falco's GBMU [RUMBLE]";
 
 const OAM_T: usize = 80;
 const DRAW_T: usize = 172;
@@ -18,6 +22,7 @@ const LAST_L_T: usize = 4;
 
 const OFF_T: usize = 70224;
 
+#[derive(Clone, Copy)]
 enum State {
     Oam,
     Draw,
@@ -35,6 +40,15 @@ enum ModFlag {
 use std::time::Instant;
 */
 
+pub struct DispSnap {
+    cycles: usize,
+    state: State,
+    sprites: Vec<Sprite>,
+    off_cy: usize,
+    win_y: usize,
+    was_off: bool,
+}
+
 pub struct Display {
     cycles: usize,
     state: State,
@@ -44,6 +58,8 @@ pub struct Display {
     off_cy: usize,
     win_y: usize,
     was_off: bool,
+    rmbl: bool,
+    shk: bool,
     /*
     time: Instant,
     time_v: Vec<u128>,
@@ -56,17 +72,14 @@ impl Display {
             cycles: 80,
             state: State::Oam,
             buff: vec![COLORS[0]; LCD_W * LCD_H],
-            win: Window::new(
-                "Falco's GBMU",
-                LCD_W * ZOOM,
-                LCD_H * ZOOM,
-                WindowOptions::default(),
-            )
-            .unwrap_or_else(|_| fatal_err("Can't open game window", 10)),
+            win: Window::new(TITLE, LCD_W * ZOOM, LCD_H * ZOOM, WindowOptions::default())
+                .unwrap_or_else(|_| fatal_err("Can't open game window", 10)),
             sprites: Vec::new(),
             off_cy: OFF_T,
             win_y: 0,
             was_off: false,
+            rmbl: false,
+            shk: false,
             /*
             time: Instant::now(),
             time_v: Vec::new(),
@@ -98,6 +111,26 @@ impl Display {
             .unwrap();
     }
 
+    pub fn snap(&self) -> DispSnap {
+        DispSnap {
+            cycles: self.cycles,
+            state: self.state,
+            sprites: self.sprites.clone(),
+            off_cy: self.off_cy,
+            win_y: self.win_y,
+            was_off: self.was_off,
+        }
+    }
+
+    pub fn restore(&mut self, snap: &DispSnap) {
+        self.cycles = snap.cycles;
+        self.state = snap.state;
+        self.sprites = snap.sprites.clone();
+        self.off_cy = snap.off_cy;
+        self.win_y = snap.win_y;
+        self.was_off = snap.was_off;
+    }
+
     fn update_ly(&mut self, m: MMy, wflag: ModFlag, lflag: ModFlag) {
         let lcdc = m.su_get(LCDC);
 
@@ -255,6 +288,7 @@ impl Display {
                         self.state = Display::update_stat(m, State::VBlank);
                         self.cycles = V_BLK_T - rem;
                         m.su_set(IF, m.su_get(IF) | 0x1);
+                        m.cheat_frame();
                     } else {
                         self.state = Display::update_stat(m, State::Oam);
                         self.cycles = OAM_T - rem;
@@ -277,9 +311,7 @@ impl Display {
                             self.time = Instant::now();
                             */
                             if !self.was_off {
-                                self.win
-                                    .update_with_buffer(&self.buff, LCD_W, LCD_H)
-                                    .unwrap();
+                                self.present(m.rumble());
                             } else {
                                 self.was_off = false;
                             }
@@ -315,6 +347,32 @@ impl Display {
         }
     }
 
+    fn present(&mut self, rmbl: bool) {
+        if rmbl != self.rmbl {
+            self.rmbl = rmbl;
+            self.win.set_title(if rmbl { TITLE_RMBL } else { TITLE });
+        }
+        if rmbl {
+            let off = if self.shk { SHAKE } else { 0 };
+            let buff: Vec<u32> = (0..LCD_W * LCD_H)
+                .map(|i| {
+                    if i % LCD_W >= off {
+                        self.buff[i - off]
+                    } else {
+                        COLORS[0]
+                    }
+                })
+                .collect();
+
+            self.shk = !self.shk;
+            self.win.update_with_buffer(&buff, LCD_W, LCD_H).unwrap();
+        } else {
+            self.win
+                .update_with_buffer(&self.buff, LCD_W, LCD_H)
+                .unwrap();
+        }
+    }
+
     fn lcd_off(&mut self, m: MMy, cy: usize) {
         if cy >= self.off_cy {
             self.update_ly(m, ModFlag::Res, ModFlag::Res);
@@ -332,3 +390,9 @@ impl Display {
         }
     }
 }
+
+impl Default for Display {
+    fn default() -> Display {
+        Display::new()
+    }
+}
@@ -0,0 +1,305 @@
+use crate::mem::*;
+use crate::reg::{api::*, *};
+use crate::sym::*;
+
+const OPS: [&str; 24] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^", "!",
+    "~", "<", ">", "(", ")", "[", "]",
+];
+const LVLS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+const RRS: [&str; 7] = ["af", "bc", "de", "hl", "sp", "pc", "ime"];
+const RS: [(&str, usize, bool); 8] = [
+    ("a", 0, U),
+    ("f", 0, D),
+    ("b", 1, U),
+    ("c", 1, D),
+    ("d", 2, U),
+    ("e", 2, D),
+    ("h", 3, U),
+    ("l", 3, D),
+];
+const FS: [(&str, u16); 5] = [("zf", Z), ("nf", N), ("hf", H), ("cf", CY), ("cy", CY)];
+const DEPTH: usize = 64;
+
+#[derive(Debug, PartialEq)]
+enum Tok {
+    Nb(i64),
+    Id(String),
+    Op(&'static str),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Nb(i64),
+    RR(usize),
+    R(usize, bool),
+    F(u16),
+    Mem(Box<Expr>),
+    Un(&'static str, Box<Expr>),
+    Bin(&'static str, Box<Expr>, Box<Expr>),
+}
+
+struct Parser<'a> {
+    toks: Vec<Tok>,
+    pos: usize,
+    depth: usize,
+    syms: &'a Syms,
+}
+
+fn lex(s: &str) -> Result<Vec<Tok>, String> {
+    let mut result = Vec::new();
+    let mut i = 0;
+
+    while i < s.len() {
+        let rest = &s[i..];
+        let c = rest.chars().next().unwrap();
+
+        if c.is_whitespace() {
+            i += c.len_utf8();
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            let nb = &rest[..len].to_lowercase();
+            let val = if let Some(hex) = nb.strip_prefix("0x") {
+                i64::from_str_radix(hex, 16)
+            } else if let Some(bin) = nb.strip_prefix("0b") {
+                i64::from_str_radix(bin, 2)
+            } else {
+                nb.parse::<i64>()
+            };
+
+            result.push(Tok::Nb(val.map_err(|_| format!("Bad number: {}", nb))?));
+            i += len;
+        } else if c.is_alphabetic() || c == '_' || c == '.' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || "_.@#$".contains(c)))
+                .unwrap_or(rest.len());
+
+            result.push(Tok::Id(String::from(&rest[..len])));
+            i += len;
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(*op)) {
+            result.push(Tok::Op(op));
+            i += op.len();
+        } else {
+            return Err(format!("Unexpected character: {}", c));
+        }
+    }
+    Ok(result)
+}
+
+impl<'a> Parser<'a> {
+    fn next_op(&self, ops: &[&str]) -> Option<&'static str> {
+        match self.toks.get(self.pos) {
+            Some(Tok::Op(op)) if ops.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.next_op(&[op]).is_some() {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}'", op))
+        }
+    }
+
+    fn bin(&mut self, lvl: usize) -> Result<Expr, String> {
+        if lvl == LVLS.len() {
+            return self.un();
+        }
+        let mut result = self.bin(lvl + 1)?;
+
+        while let Some(op) = self.next_op(LVLS[lvl]) {
+            self.pos += 1;
+            result = Expr::Bin(op, Box::new(result), Box::new(self.bin(lvl + 1)?));
+        }
+        Ok(result)
+    }
+
+    fn un(&mut self) -> Result<Expr, String> {
+        if self.depth == DEPTH {
+            return Err(String::from("Expression nested too deeply"));
+        }
+        self.depth += 1;
+        let result = self.term();
+
+        self.depth -= 1;
+        result
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        if let Some(op) = self.next_op(&["-", "!", "~"]) {
+            self.pos += 1;
+            return Ok(Expr::Un(op, Box::new(self.un()?)));
+        }
+        self.pos += 1;
+        match self.toks.get(self.pos - 1) {
+            Some(Tok::Nb(nb)) => Ok(Expr::Nb(*nb)),
+            Some(Tok::Id(id)) => self.id(id),
+            Some(Tok::Op("(")) => {
+                let result = self.bin(0)?;
+
+                self.expect(")")?;
+                Ok(Expr::Mem(Box::new(result)))
+            }
+            Some(Tok::Op("[")) => {
+                let result = self.bin(0)?;
+
+                self.expect("]")?;
+                Ok(result)
+            }
+            Some(Tok::Op(op)) => Err(format!("Unexpected '{}'", op)),
+            None => Err(String::from("Unexpected end of expression")),
+        }
+    }
+
+    fn id(&self, id: &str) -> Result<Expr, String> {
+        let lc = id.to_lowercase();
+
+        if let Some(i) = RRS.iter().position(|r| *r == lc) {
+            Ok(Expr::RR(i))
+        } else if let Some(r) = RS.iter().find(|r| r.0 == lc) {
+            Ok(Expr::R(r.1, r.2))
+        } else if let Some(f) = FS.iter().find(|f| f.0 == lc) {
+            Ok(Expr::F(f.1))
+        } else if let Some((_, addr)) = self.syms.find(id) {
+            Ok(Expr::Nb(addr as i64))
+        } else if let Some(addr) = io_addr(id) {
+            Ok(Expr::Nb(addr as i64))
+        } else {
+            Err(format!("Unknown symbol: {}", id))
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(s: &str, syms: &Syms) -> Result<Expr, String> {
+        let mut parser = Parser {
+            toks: lex(s)?,
+            pos: 0,
+            depth: 0,
+            syms,
+        };
+        let result = parser.bin(0)?;
+
+        match parser.toks.get(parser.pos) {
+            None => Ok(result),
+            Some(Tok::Op(op)) => Err(format!("Unexpected '{}'", op)),
+            Some(_) => Err(String::from("Missing operator")),
+        }
+    }
+
+    pub fn eval(&self, m: My, r: &Regs) -> Result<i64, String> {
+        let regs = [&r.af, &r.bc, &r.de, &r.hl, &r.sp, &r.pc, &r.ime];
+
+        Ok(match self {
+            Expr::Nb(nb) => *nb,
+            Expr::RR(i) => grr(regs[*i]) as i64,
+            Expr::R(i, up) => gr((regs[*i], *up)) as i64,
+            Expr::F(mask) => gf((&r.af, *mask)) as i64,
+            Expr::Mem(addr) => m.su_get(addr.eval(m, r)? as u16) as i64,
+            Expr::Un(op, e) => {
+                let v = e.eval(m, r)?;
+
+                match *op {
+                    "-" => v.wrapping_neg(),
+                    "!" => (v == 0) as i64,
+                    _ => !v,
+                }
+            }
+            Expr::Bin("&&", lhs, rhs) => (lhs.eval(m, r)? != 0 && rhs.eval(m, r)? != 0) as i64,
+            Expr::Bin("||", lhs, rhs) => (lhs.eval(m, r)? != 0 || rhs.eval(m, r)? != 0) as i64,
+            Expr::Bin(op, lhs, rhs) => {
+                let (l, r) = (lhs.eval(m, r)?, rhs.eval(m, r)?);
+
+                match *op {
+                    "|" => l | r,
+                    "^" => l ^ r,
+                    "&" => l & r,
+                    "==" => (l == r) as i64,
+                    "!=" => (l != r) as i64,
+                    "<" => (l < r) as i64,
+                    "<=" => (l <= r) as i64,
+                    ">" => (l > r) as i64,
+                    ">=" => (l >= r) as i64,
+                    "<<" => l.wrapping_shl(r as u32),
+                    ">>" => l.wrapping_shr(r as u32),
+                    "+" => l.wrapping_add(r),
+                    "-" => l.wrapping_sub(r),
+                    "*" => l.wrapping_mul(r),
+                    _ if r == 0 => return Err(String::from("Division by zero")),
+                    "/" => l.wrapping_div(r),
+                    _ => l.wrapping_rem(r),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval() {
+        let mut mem = Mem::new("");
+        let mut regs = Regs::new();
+        let syms = Syms::parse("00:c000 wPlayerX\n01:4000 MainLoop\n");
+        let ev = |s: &str, m: My, r: &Regs| Expr::parse(s, &syms).and_then(|e| e.eval(m, r));
+
+        srr(&mut regs.af, 0x10b0);
+        srr(&mut regs.hl, 0xc000);
+        mem.nu_set(0xc000, 0x04);
+        assert_eq!(ev("a == 0x10 && (hl) > 3", &mem, &regs), Ok(1));
+        assert_eq!(ev("a == 0x10 && (hl) > 4", &mem, &regs), Ok(0));
+        mem.wtchs.push(Wtch {
+            start: 0xc000,
+            end: 0xc000,
+            rd: true,
+            wr: false,
+        });
+        assert_eq!(ev("(0xc000) == 4", &mem, &regs), Ok(1));
+        assert!(mem.wtch_hits.borrow().is_empty());
+        assert_eq!(ev("1 + 2 * 3 - 0b100", &mem, &regs), Ok(3));
+        assert_eq!(ev("[1 + 2] * 3", &mem, &regs), Ok(9));
+        assert_eq!(ev("-1 & 0xff | 1 << 8", &mem, &regs), Ok(0x1ff));
+        assert_eq!(ev("(wPlayerX) + MainLoop", &mem, &regs), Ok(0x4004));
+        assert_eq!(ev("zf && !nf && cy", &mem, &regs), Ok(1));
+        assert_eq!(ev("h + l", &mem, &regs), Ok(0xc0));
+        assert_eq!(ev("lcdc", &mem, &regs), Ok(0xff40));
+        assert_eq!(
+            ev("10 % 0", &mem, &regs),
+            Err(String::from("Division by zero"))
+        );
+        assert_eq!(ev("0 && 1 / 0", &mem, &regs), Ok(0));
+        assert!(ev("foo + 1", &mem, &regs).is_err());
+        assert!(ev("(hl", &mem, &regs).is_err());
+        assert!(ev("a b", &mem, &regs).is_err());
+        assert!(ev("1 +", &mem, &regs).is_err());
+        assert!(ev("0xzz", &mem, &regs).is_err());
+        assert!(ev(&format!("{}1", "-".repeat(100000)), &mem, &regs).is_err());
+        assert!(ev(
+            &format!("{}1{}", "[".repeat(100), "]".repeat(100)),
+            &mem,
+            &regs
+        )
+        .is_err());
+        assert_eq!(
+            ev(&format!("{}1", "-".repeat(DEPTH - 1)), &mem, &regs),
+            Ok(-1)
+        );
+    }
+}
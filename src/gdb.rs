@@ -0,0 +1,326 @@
+use crate::mem::*;
+use crate::reg::{api::*, *};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const REGS_NB: usize = 13;
+const PKT_SZ: usize = 0x4000;
+
+pub enum GdbAct {
+    Cont,
+    Step,
+    BrkAdd(u16),
+    BrkDel(u16),
+    Modif,
+    Detach,
+    Kill,
+}
+
+pub struct Gdb {
+    strm: TcpStream,
+    buff: Vec<u8>,
+    pend: Option<u8>,
+}
+
+fn cksum(data: &str) -> u8 {
+    data.bytes().fold(0, |acc, b| acc.wrapping_add(b))
+}
+
+fn hex_to_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn regs_get(r: &Regs) -> [u16; REGS_NB] {
+    let mut result = [0; REGS_NB];
+
+    result[..6].copy_from_slice(&[
+        grr(&r.af),
+        grr(&r.bc),
+        grr(&r.de),
+        grr(&r.hl),
+        grr(&r.sp),
+        grr(&r.pc),
+    ]);
+    result
+}
+
+fn reg_set(r: &mut Regs, idx: usize, val: u16) {
+    match idx {
+        0 => srr(&mut r.af, val & 0xfff0),
+        1 => srr(&mut r.bc, val),
+        2 => srr(&mut r.de, val),
+        3 => srr(&mut r.hl, val),
+        4 => srr(&mut r.sp, val),
+        5 => srr(&mut r.pc, val),
+        _ => (),
+    }
+}
+
+fn regs_to_hex(r: &Regs) -> String {
+    regs_get(r)
+        .iter()
+        .map(|v| format!("{:02x}{:02x}", *v as u8, v >> 8))
+        .collect()
+}
+
+fn addr_len(s: &str) -> Option<(u16, usize)> {
+    let (addr, len) = s.split_once(',')?;
+
+    Some((
+        u16::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+// GDB sees memory as the CPU does, minus the watchpoints and the CDL data marks
+fn mem_rd(m: &Mem, args: &str) -> Option<String> {
+    let (addr, len) = addr_len(args)?;
+    let hits = m.wtch_hits.borrow().len();
+    let result = (0..len.min(0x10000 - addr as usize).min(PKT_SZ / 2))
+        .map(|i| addr + i as u16)
+        .map(|a| format!("{:02x}", if a < 0x8000 { m.su_get(a) } else { m.nu_get(a) }))
+        .collect();
+
+    m.wtch_hits.borrow_mut().truncate(hits);
+    Some(result)
+}
+
+fn mem_wr(m: &mut Mem, args: &str) -> Option<()> {
+    let (al, data) = args.split_once(':')?;
+    let ((addr, len), data) = (addr_len(al)?, hex_to_bytes(data)?);
+
+    if data.len() != len || addr < 0x8000 || addr as usize + len > 0x10000 {
+        return None;
+    }
+    let hits = m.wtch_hits.borrow().len();
+
+    for (i, b) in data.iter().enumerate() {
+        m.nu_set(addr + i as u16, *b);
+    }
+    m.wtch_hits.borrow_mut().truncate(hits);
+    Some(())
+}
+
+impl Gdb {
+    pub fn listen(port: u16) -> Result<Gdb, String> {
+        let lst = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|_| format!("Can't listen on port {}", port))?;
+
+        println!("Waiting for GDB on 127.0.0.1:{}...", port);
+        let (strm, addr) = lst.accept().map_err(|_| String::from("Can't accept GDB"))?;
+
+        println!("GDB attached from {}", addr);
+        strm.set_nodelay(true).ok();
+        Ok(Gdb {
+            strm,
+            buff: Vec::new(),
+            pend: None,
+        })
+    }
+
+    pub fn interrupted(&mut self) -> bool {
+        let mut byte = [0u8];
+
+        if self.strm.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let result = match self.strm.read(&mut byte) {
+            Ok(1) if byte[0] == 0x03 => true,
+            Ok(1) => {
+                self.pend = Some(byte[0]);
+                false
+            }
+            _ => false,
+        };
+
+        self.strm.set_nonblocking(false).ok();
+        result
+    }
+
+    pub fn send(&mut self, data: &str) -> bool {
+        let pkt = format!("${}#{:02x}", data, cksum(data));
+
+        self.strm.write_all(pkt.as_bytes()).is_ok()
+    }
+
+    fn recv(&mut self) -> Option<String> {
+        let mut byte = [0u8];
+
+        loop {
+            if let Some(b) = self.pend.take() {
+                byte[0] = b;
+            } else {
+                match self.strm.read(&mut byte) {
+                    Ok(0) => return None,
+                    Ok(_) => (),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => return None,
+                }
+            }
+            match byte[0] {
+                b'$' => self.buff.clear(),
+                b'#' => {
+                    let mut cs = [0u8; 2];
+
+                    self.strm.read_exact(&mut cs).ok()?;
+                    let data = String::from_utf8_lossy(&self.buff).into_owned();
+                    let ok = std::str::from_utf8(&cs)
+                        .ok()
+                        .and_then(|cs| u8::from_str_radix(cs, 16).ok())
+                        == Some(cksum(&data));
+
+                    self.strm.write_all(if ok { b"+" } else { b"-" }).ok()?;
+                    if ok {
+                        return Some(data);
+                    }
+                }
+                0x03 => return Some(String::from("?")),
+                b => self.buff.push(b),
+            }
+        }
+    }
+
+    pub fn handle(&mut self, m: &mut Mem, r: &mut Regs) -> Option<GdbAct> {
+        loop {
+            let pkt = self.recv()?;
+            let (cmd, args) = pkt.split_at(pkt.len().min(1));
+            let mut act = None;
+            let resp = match cmd {
+                "?" => String::from("S05"),
+                "g" => regs_to_hex(r),
+                "G" => match hex_to_bytes(args) {
+                    Some(bytes) => {
+                        for (i, v) in bytes.chunks(2).enumerate().filter(|c| c.1.len() == 2) {
+                            reg_set(r, i, v[0] as u16 | (v[1] as u16) << 8);
+                        }
+                        act = Some(GdbAct::Modif);
+                        String::from("OK")
+                    }
+                    None => String::from("E01"),
+                },
+                "p" => match usize::from_str_radix(args, 16) {
+                    Ok(i) if i < REGS_NB => {
+                        let v = regs_get(r)[i];
+
+                        format!("{:02x}{:02x}", v as u8, v >> 8)
+                    }
+                    _ => String::from("E01"),
+                },
+                "P" => match args
+                    .split_once('=')
+                    .and_then(|(i, v)| Some((usize::from_str_radix(i, 16).ok()?, hex_to_bytes(v)?)))
+                {
+                    Some((i, v)) if v.len() == 2 => {
+                        reg_set(r, i, v[0] as u16 | (v[1] as u16) << 8);
+                        act = Some(GdbAct::Modif);
+                        String::from("OK")
+                    }
+                    _ => String::from("E01"),
+                },
+                "m" => mem_rd(m, args).unwrap_or_else(|| String::from("E01")),
+                "M" => match mem_wr(m, args) {
+                    Some(()) => {
+                        act = Some(GdbAct::Modif);
+                        String::from("OK")
+                    }
+                    None => String::from("E01"),
+                },
+                "Z" | "z" if args.starts_with("0,") || args.starts_with("1,") => {
+                    match addr_len(&args[2..]) {
+                        Some((addr, _)) => {
+                            act = Some(if cmd == "Z" {
+                                GdbAct::BrkAdd(addr)
+                            } else {
+                                GdbAct::BrkDel(addr)
+                            });
+                            String::from("OK")
+                        }
+                        None => String::from("E01"),
+                    }
+                }
+                "c" | "s" => {
+                    if let Ok(addr) = u16::from_str_radix(args, 16) {
+                        srr(&mut r.pc, addr);
+                    }
+                    return Some(if cmd == "c" {
+                        GdbAct::Cont
+                    } else {
+                        GdbAct::Step
+                    });
+                }
+                "D" => {
+                    self.send("OK");
+                    return Some(GdbAct::Detach);
+                }
+                "k" => return Some(GdbAct::Kill),
+                "H" | "T" => String::from("OK"),
+                "q" if args.starts_with("Supported") => format!("PacketSize={:x}", PKT_SZ),
+                "q" if args == "Attached" => String::from("1"),
+                "q" if args == "fThreadInfo" => String::from("m1"),
+                "q" if args == "sThreadInfo" => String::from("l"),
+                "q" if args == "C" => String::from("QC1"),
+                _ => String::new(),
+            };
+
+            if !self.send(&resp) {
+                return None;
+            }
+            if act.is_some() {
+                return act;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let mut regs = Regs::new();
+
+        assert_eq!(cksum("OK"), 0x9a);
+        assert_eq!(hex_to_bytes("00ff1a"), Some(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(hex_to_bytes("0f1"), None);
+        assert_eq!(addr_len("c000,10"), Some((0xc000, 0x10)));
+        assert_eq!(addr_len("c000"), None);
+        assert_eq!(
+            regs_to_hex(&regs),
+            format!("b0011300d8004d01feff0000{}", "0000".repeat(7))
+        );
+        reg_set(&mut regs, 0, 0x12ff);
+        reg_set(&mut regs, 5, 0x0150);
+        reg_set(&mut regs, 12, 0xdead);
+        assert_eq!(grr(&regs.af), 0x12f0);
+        assert_eq!(grr(&regs.pc), 0x0150);
+    }
+
+    #[test]
+    fn mem() {
+        let mut mem = Mem::new("");
+
+        assert_eq!(mem_wr(&mut mem, "c000,2:1234"), Some(()));
+        assert_eq!(mem_rd(&mem, "c000,2"), Some(String::from("1234")));
+        assert_eq!(mem_wr(&mut mem, "2000,1:05"), None);
+        assert_eq!(mem_wr(&mut mem, "ffff,2:0000"), None);
+        assert_eq!(mem_wr(&mut mem, "c000,2:12"), None);
+        assert_eq!(mem_rd(&mem, "fffe,10").map(|s| s.len()), Some(4));
+        assert_eq!(mem_rd(&mem, "0,ffffffff").map(|s| s.len()), Some(PKT_SZ));
+        mem.wtchs.push(Wtch {
+            start: 0xc000,
+            end: 0xffff,
+            rd: true,
+            wr: true,
+        });
+        assert_eq!(mem_wr(&mut mem, "ff04,1:55"), Some(()));
+        assert_eq!(mem_rd(&mem, "ff04,1"), Some(String::from("00")));
+        assert!(mem.wtch_hits.borrow().is_empty());
+    }
+}
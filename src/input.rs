@@ -1,12 +1,78 @@
 use crate::mem::*;
 use crate::reg::api::*;
-use minifb::{Key, Window};
+use minifb::{Key, MouseMode, Window};
+use std::collections::VecDeque;
+
+const TILT: f32 = 112.;
+const KEY_NMS: [(&str, Key); 40] = [
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("a", Key::A),
+    ("b", Key::B),
+    ("c", Key::C),
+    ("d", Key::D),
+    ("e", Key::E),
+    ("f", Key::F),
+    ("g", Key::G),
+    ("h", Key::H),
+    ("i", Key::I),
+    ("j", Key::J),
+    ("k", Key::K),
+    ("l", Key::L),
+    ("m", Key::M),
+    ("n", Key::N),
+    ("o", Key::O),
+    ("p", Key::P),
+    ("q", Key::Q),
+    ("r", Key::R),
+    ("s", Key::S),
+    ("t", Key::T),
+    ("u", Key::U),
+    ("v", Key::V),
+    ("w", Key::W),
+    ("x", Key::X),
+    ("y", Key::Y),
+    ("z", Key::Z),
+    ("kp0", Key::NumPad0),
+    ("kp1", Key::NumPad1),
+    ("kp2", Key::NumPad2),
+    ("kp3", Key::NumPad3),
+    ("kp4", Key::NumPad4),
+    ("kp5", Key::NumPad5),
+    ("kp6", Key::NumPad6),
+    ("kp7", Key::NumPad7),
+    ("kp8", Key::NumPad8),
+    ("kp9", Key::NumPad9),
+];
+
+pub fn key_nm(nm: &str) -> Option<Key> {
+    KEY_NMS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(nm))
+        .map(|(_, k)| *k)
+}
+
+pub fn key_str(key: Key) -> &'static str {
+    KEY_NMS
+        .iter()
+        .find(|(_, k)| *k == key)
+        .map_or("?", |(n, _)| n)
+}
 
 pub struct Inputs {
     pub keys: Option<Vec<Key>>,
     acts: Vec<Key>,
     dirs: Vec<Key>,
     brk: bool,
+    pub n: u64,
+    hist: VecDeque<Option<Vec<Key>>>,
+    hist_base: u64,
+    pub rec: bool,
+    pub tilt_keys: [Key; 4],
+    pub tilt_mouse: bool,
+    pub tilt: (i16, i16),
 }
 
 impl Inputs {
@@ -16,11 +82,33 @@ impl Inputs {
             acts: vec![Key::D, Key::F, Key::A, Key::S],
             dirs: vec![Key::L, Key::J, Key::I, Key::K],
             brk: false,
+            n: 0,
+            hist: VecDeque::new(),
+            hist_base: 0,
+            rec: false,
+            tilt_keys: [Key::Left, Key::Right, Key::Up, Key::Down],
+            tilt_mouse: false,
+            tilt: (0, 0),
         }
     }
 
     pub fn up_keys(m: MMy, win: &Window) {
-        let new_keys = win.get_keys();
+        let idx = (m.inputs.n - m.inputs.hist_base) as usize;
+        let new_keys = match m.inputs.hist.get(idx) {
+            Some(keys) => keys.clone(),
+            None => {
+                let keys = win.get_keys();
+
+                if m.inputs.rec {
+                    m.inputs.hist.push_back(keys.clone());
+                } else {
+                    m.inputs.hist_base = m.inputs.n + 1;
+                }
+                keys
+            }
+        };
+
+        m.inputs.n += 1;
 
         if let Some(n_keys) = &new_keys {
             if n_keys.len() != 0 {
@@ -39,6 +127,48 @@ impl Inputs {
             }
         }
         m.inputs.keys = new_keys;
+        m.inputs.tilt = if m.inputs.tilt_mouse {
+            Inputs::tilt_mouse(win)
+        } else {
+            Inputs::tilt_keys(&m.inputs)
+        };
+        m.tilt(m.inputs.tilt);
+    }
+
+    fn tilt_keys(&self) -> (i16, i16) {
+        let held = |k: usize| {
+            self.keys
+                .as_ref()
+                .is_some_and(|keys| keys.contains(&self.tilt_keys[k])) as i16 as f32
+        };
+
+        (
+            ((held(0) - held(1)) * TILT) as i16,
+            ((held(2) - held(3)) * TILT) as i16,
+        )
+    }
+
+    fn tilt_mouse(win: &Window) -> (i16, i16) {
+        let (w, h) = win.get_size();
+
+        match win.get_mouse_pos(MouseMode::Clamp) {
+            Some((x, y)) => (
+                ((1. - x / w as f32 * 2.) * TILT) as i16,
+                ((1. - y / h as f32 * 2.) * TILT) as i16,
+            ),
+            None => (0, 0),
+        }
+    }
+
+    pub fn trim_hist(&mut self, n: u64) {
+        while self.hist_base < n && !self.hist.is_empty() {
+            self.hist.pop_front();
+            self.hist_base += 1;
+        }
+    }
+
+    pub fn cut_hist(&mut self) {
+        self.hist.truncate((self.n - self.hist_base) as usize);
     }
 
     pub fn get_p1(m: My) -> u8 {
@@ -88,3 +218,29 @@ impl Inputs {
         result
     }
 }
+
+impl Default for Inputs {
+    fn default() -> Inputs {
+        Inputs::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tilt() {
+        let mut inputs = Inputs::new();
+
+        assert_eq!(key_nm("KP4"), Some(Key::NumPad4));
+        assert_eq!(key_nm("f1"), None);
+        assert_eq!(key_str(Key::Left), "left");
+        assert_eq!(inputs.tilt_keys(), (0, 0));
+        inputs.keys = Some(vec![Key::Right, Key::Up]);
+        assert_eq!(inputs.tilt_keys(), (-112, 112));
+        inputs.tilt_keys = [Key::A, Key::D, Key::W, Key::S];
+        inputs.keys = Some(vec![Key::A, Key::S]);
+        assert_eq!(inputs.tilt_keys(), (112, -112));
+    }
+}
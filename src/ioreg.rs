@@ -0,0 +1,408 @@
+use crate::mem::*;
+use crate::reg::api::*;
+use crate::utils::*;
+
+#[derive(PartialEq, Clone, Copy)]
+enum Sub {
+    Joy,
+    Ser,
+    Tim,
+    Int,
+    Snd,
+    Lcd,
+    Cgb,
+    Oth,
+}
+
+const SUBS: [(Sub, &str, &str); 8] = [
+    (Sub::Joy, "joypad", "Joypad"),
+    (Sub::Ser, "serial", "Serial"),
+    (Sub::Tim, "timer", "Timer"),
+    (Sub::Int, "int", "Interrupts"),
+    (Sub::Snd, "sound", "Sound"),
+    (Sub::Lcd, "lcd", "LCD"),
+    (Sub::Cgb, "cgb", "CGB"),
+    (Sub::Oth, "other", "Other"),
+];
+
+pub const INTS: [&str; 5] = ["VBlank", "STAT", "Timer", "Serial", "Joypad"];
+
+fn bits(val: u8, nms: &[(u8, &str)]) -> String {
+    let set: Vec<&str> = nms
+        .iter()
+        .filter(|(msk, _)| val & msk != 0)
+        .map(|(_, nm)| *nm)
+        .collect();
+
+    if set.is_empty() {
+        String::from("none")
+    } else {
+        set.join(" ")
+    }
+}
+
+fn on(val: u8, msk: u8) -> &'static str {
+    if val & msk != 0 {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn dec_p1(v: u8) -> String {
+    format!(
+        "dir:{} act:{} pressed:{}",
+        on(!v, 0x10),
+        on(!v, 0x20),
+        bits(!v & 0xf, &[(0x1, "0"), (0x2, "1"), (0x4, "2"), (0x8, "3")])
+    )
+}
+
+fn dec_sc(v: u8) -> String {
+    format!(
+        "transfer:{} clock:{}",
+        on(v, 0x80),
+        if v & 0x1 != 0 { "internal" } else { "external" }
+    )
+}
+
+fn dec_tac(v: u8) -> String {
+    format!(
+        "timer:{} freq:{}Hz",
+        on(v, 0x4),
+        [4096, 262144, 65536, 16384][(v & 0x3) as usize]
+    )
+}
+
+fn dec_int(v: u8) -> String {
+    bits(
+        v,
+        &[
+            (0x1, INTS[0]),
+            (0x2, INTS[1]),
+            (0x4, INTS[2]),
+            (0x8, INTS[3]),
+            (0x10, INTS[4]),
+        ],
+    )
+}
+
+fn dec_nr10(v: u8) -> String {
+    format!(
+        "sweep time:{} dir:{} shift:{}",
+        (v >> 4) & 0x7,
+        if v & 0x8 != 0 { "dec" } else { "inc" },
+        v & 0x7
+    )
+}
+
+fn dec_duty(v: u8) -> String {
+    format!(
+        "duty:{} len:{}",
+        ["12.5%", "25%", "50%", "75%"][(v >> 6) as usize],
+        v & 0x3f
+    )
+}
+
+fn dec_env(v: u8) -> String {
+    format!(
+        "vol:{} dir:{} sweep:{}",
+        v >> 4,
+        if v & 0x8 != 0 { "up" } else { "down" },
+        v & 0x7
+    )
+}
+
+fn dec_freq_hi(v: u8) -> String {
+    format!(
+        "trigger:{} len en:{} freq hi:{}",
+        on(v, 0x80),
+        on(v, 0x40),
+        v & 0x7
+    )
+}
+
+fn dec_nr30(v: u8) -> String {
+    format!("dac:{}", on(v, 0x80))
+}
+
+fn dec_nr32(v: u8) -> String {
+    format!(
+        "level:{}",
+        ["mute", "100%", "50%", "25%"][((v >> 5) & 0x3) as usize]
+    )
+}
+
+fn dec_len(v: u8) -> String {
+    format!("len:{}", v & 0x3f)
+}
+
+fn dec_nr43(v: u8) -> String {
+    format!(
+        "shift:{} width:{} ratio:{}",
+        v >> 4,
+        if v & 0x8 != 0 { 7 } else { 15 },
+        v & 0x7
+    )
+}
+
+fn dec_nr44(v: u8) -> String {
+    format!("trigger:{} len en:{}", on(v, 0x80), on(v, 0x40))
+}
+
+fn dec_nr50(v: u8) -> String {
+    format!(
+        "vin L:{} vol L:{} vin R:{} vol R:{}",
+        on(v, 0x80),
+        (v >> 4) & 0x7,
+        on(v, 0x8),
+        v & 0x7
+    )
+}
+
+fn dec_nr51(v: u8) -> String {
+    format!(
+        "L:{} R:{}",
+        bits(v >> 4, &[(0x1, "1"), (0x2, "2"), (0x4, "3"), (0x8, "4")]),
+        bits(v & 0xf, &[(0x1, "1"), (0x2, "2"), (0x4, "3"), (0x8, "4")])
+    )
+}
+
+fn dec_nr52(v: u8) -> String {
+    format!(
+        "sound:{} playing:{}",
+        on(v, 0x80),
+        bits(v & 0xf, &[(0x1, "1"), (0x2, "2"), (0x4, "3"), (0x8, "4")])
+    )
+}
+
+fn dec_lcdc(v: u8) -> String {
+    format!(
+        "LCD:{} WIN_MAP:{} WIN:{} TILES:{} BG_MAP:{} OBJ_SZ:{} OBJ:{} BG:{}",
+        on(v, 0x80),
+        if v & 0x40 != 0 { "9c00" } else { "9800" },
+        on(v, 0x20),
+        if v & 0x10 != 0 { "8000" } else { "8800" },
+        if v & 0x8 != 0 { "9c00" } else { "9800" },
+        if v & 0x4 != 0 { "8x16" } else { "8x8" },
+        on(v, 0x2),
+        on(v, 0x1)
+    )
+}
+
+fn dec_stat(v: u8) -> String {
+    format!(
+        "mode:{} LYC=LY:{} sources:{}",
+        ["HBlank", "VBlank", "OAM", "Draw"][(v & 0x3) as usize],
+        on(v, 0x4),
+        bits(
+            v,
+            &[
+                (0x40, "LYC"),
+                (0x20, "OAM"),
+                (0x10, "VBlank"),
+                (0x8, "HBlank")
+            ]
+        )
+    )
+}
+
+fn dec_dec(v: u8) -> String {
+    format!("{}", v)
+}
+
+fn dec_pal(v: u8) -> String {
+    let mut result = String::new();
+
+    for i in 0..4 {
+        let shd = (v >> (i * 2)) & 0x3;
+        let col = COLORS[shd as usize + 1];
+
+        result.push_str(&format!(
+            "{}:{} \x1b[48;2;{};{};{}m  \x1b[0m ",
+            i,
+            shd,
+            (col >> 16) & 0xff,
+            (col >> 8) & 0xff,
+            col & 0xff
+        ));
+    }
+    result
+}
+
+fn dec_key1(v: u8) -> String {
+    format!(
+        "speed:{} switch:{}",
+        if v & 0x80 != 0 { "double" } else { "normal" },
+        on(v, 0x1)
+    )
+}
+
+fn dec_bank(v: u8) -> String {
+    format!("bank:{}", v)
+}
+
+fn dec_hdma5(v: u8) -> String {
+    format!(
+        "mode:{} len:{}",
+        if v & 0x80 != 0 { "hblank" } else { "general" },
+        ((v & 0x7f) as usize + 1) * 0x10
+    )
+}
+
+fn dec_cps(v: u8) -> String {
+    format!("index:{} auto inc:{}", v & 0x3f, on(v, 0x80))
+}
+
+type Dec = Option<fn(u8) -> String>;
+
+const REGS: [(u16, Sub, Dec); 56] = [
+    (P1, Sub::Joy, Some(dec_p1)),
+    (SB, Sub::Ser, None),
+    (SC, Sub::Ser, Some(dec_sc)),
+    (DIV, Sub::Tim, None),
+    (TIMA, Sub::Tim, None),
+    (TMA, Sub::Tim, None),
+    (TAC, Sub::Tim, Some(dec_tac)),
+    (IF, Sub::Int, Some(dec_int)),
+    (IE, Sub::Int, Some(dec_int)),
+    (NR10, Sub::Snd, Some(dec_nr10)),
+    (NR11, Sub::Snd, Some(dec_duty)),
+    (NR12, Sub::Snd, Some(dec_env)),
+    (NR13, Sub::Snd, None),
+    (NR14, Sub::Snd, Some(dec_freq_hi)),
+    (NR21, Sub::Snd, Some(dec_duty)),
+    (NR22, Sub::Snd, Some(dec_env)),
+    (NR23, Sub::Snd, None),
+    (NR24, Sub::Snd, Some(dec_freq_hi)),
+    (NR30, Sub::Snd, Some(dec_nr30)),
+    (NR31, Sub::Snd, None),
+    (NR32, Sub::Snd, Some(dec_nr32)),
+    (NR33, Sub::Snd, None),
+    (NR34, Sub::Snd, Some(dec_freq_hi)),
+    (NR41, Sub::Snd, Some(dec_len)),
+    (NR42, Sub::Snd, Some(dec_env)),
+    (NR43, Sub::Snd, Some(dec_nr43)),
+    (NR44, Sub::Snd, Some(dec_nr44)),
+    (NR50, Sub::Snd, Some(dec_nr50)),
+    (NR51, Sub::Snd, Some(dec_nr51)),
+    (NR52, Sub::Snd, Some(dec_nr52)),
+    (LCDC, Sub::Lcd, Some(dec_lcdc)),
+    (STAT, Sub::Lcd, Some(dec_stat)),
+    (SCY, Sub::Lcd, Some(dec_dec)),
+    (SCX, Sub::Lcd, Some(dec_dec)),
+    (LY, Sub::Lcd, Some(dec_dec)),
+    (LYC, Sub::Lcd, Some(dec_dec)),
+    (DMA, Sub::Lcd, None),
+    (BGP, Sub::Lcd, Some(dec_pal)),
+    (OBP0, Sub::Lcd, Some(dec_pal)),
+    (OBP1, Sub::Lcd, Some(dec_pal)),
+    (WY, Sub::Lcd, Some(dec_dec)),
+    (WX, Sub::Lcd, Some(dec_dec)),
+    (KEY1, Sub::Cgb, Some(dec_key1)),
+    (VBK, Sub::Cgb, Some(dec_bank)),
+    (BOOT, Sub::Cgb, None),
+    (HDMA1, Sub::Cgb, None),
+    (HDMA2, Sub::Cgb, None),
+    (HDMA3, Sub::Cgb, None),
+    (HDMA4, Sub::Cgb, None),
+    (HDMA5, Sub::Cgb, Some(dec_hdma5)),
+    (RP, Sub::Cgb, None),
+    (BCPS, Sub::Cgb, Some(dec_cps)),
+    (BCPD, Sub::Cgb, None),
+    (OCPS, Sub::Cgb, Some(dec_cps)),
+    (OCPD, Sub::Cgb, None),
+    (SVBK, Sub::Cgb, Some(dec_bank)),
+];
+
+fn read(m: My, addr: u16) -> u8 {
+    if addr == P1 {
+        m.nu_get(P1)
+    } else {
+        m.su_get(addr)
+    }
+}
+
+pub fn io_to_str(m: My, filt: &str) -> Option<String> {
+    let subs: Vec<&(Sub, &str, &str)> = SUBS
+        .iter()
+        .filter(|s| filt.is_empty() || s.1 == filt)
+        .collect();
+    let mut result = String::from("-------------------------------------------------------");
+
+    if subs.is_empty() {
+        return None;
+    }
+    for (sub, _, title) in subs {
+        result.push_str(&format!("\n  {}:\n", title));
+        for (addr, _, dec) in REGS.iter().filter(|r| r.1 == *sub) {
+            let val = read(m, *addr);
+
+            result.push_str(&format!(
+                "    {:<5} (0x{:04x}) = 0x{:02x}  {}\n",
+                io_nm(*addr).unwrap_or("?"),
+                addr,
+                val,
+                dec.map(|d| d(val)).unwrap_or_default()
+            ));
+        }
+        match sub {
+            Sub::Snd => {
+                result.push_str(&format!("    {:<5} (0x{:04x}) =", "WPRAM", WPRAM));
+                for addr in WPRAM..WPRAM + 0x10 {
+                    result.push_str(&format!(" {:02x}", read(m, addr)));
+                }
+                result.push('\n');
+            }
+            Sub::Oth => {
+                let unk: Vec<u16> = (0xff00..0xff80)
+                    .filter(|a| !REGS.iter().any(|r| r.0 == *a))
+                    .filter(|a| !(WPRAM..WPRAM + 0x10).contains(a))
+                    .collect();
+
+                for (i, addr) in unk.iter().enumerate() {
+                    result.push_str(&format!(
+                        "{}{:04x}={:02x}",
+                        if i % 8 == 0 { "    " } else { " " },
+                        addr,
+                        read(m, *addr)
+                    ));
+                    if i % 8 == 7 || i == unk.len() - 1 {
+                        result.push('\n');
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    result.push_str("-------------------------------------------------------");
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(
+            dec_lcdc(0x91),
+            "LCD:on WIN_MAP:9800 WIN:off TILES:8000 BG_MAP:9800 OBJ_SZ:8x8 OBJ:off BG:on"
+        );
+        assert_eq!(dec_stat(0x45), "mode:VBlank LYC=LY:on sources:LYC");
+        assert_eq!(dec_tac(0x05), "timer:on freq:262144Hz");
+        assert_eq!(dec_int(0x00), "none");
+        assert_eq!(dec_int(0x15), "VBlank Timer Joypad");
+        assert_eq!(dec_nr51(0xf3), "L:1 2 3 4 R:1 2");
+    }
+
+    #[test]
+    fn filter() {
+        let mem = Mem::new("");
+
+        assert!(io_to_str(&mem, "").is_some());
+        assert!(io_to_str(&mem, "lcd").unwrap().contains("LCDC"));
+        assert!(!io_to_str(&mem, "lcd").unwrap().contains("NR10"));
+        assert!(io_to_str(&mem, "vram").is_none());
+    }
+}
@@ -0,0 +1,24 @@
+pub mod cdl;
+pub mod cheat;
+pub mod cmd;
+pub mod debug;
+pub mod disasm;
+pub mod disp;
+pub mod expr;
+pub mod gdb;
+pub mod header;
+pub mod input;
+pub mod ioreg;
+pub mod mbc;
+pub mod mem;
+pub mod memed;
+pub mod ops;
+pub mod prof;
+pub mod reg;
+pub mod search;
+pub mod sound;
+pub mod sprite;
+pub mod sym;
+pub mod timer;
+pub mod trace;
+pub mod utils;
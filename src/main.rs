@@ -1,28 +1,16 @@
-mod debug;
-mod disp;
-mod header;
-mod input;
-mod mbc;
-mod mem;
-mod ops;
-mod reg;
-mod sound;
-mod sprite;
-mod timer;
-mod utils;
-
-use debug::*;
-use disp::*;
-use header::*;
-use mem::*;
-use ops::imp::{dec_rr, rst};
-use ops::ops::*;
-use reg::{api::*, *};
-use sound::*;
+use gbmu::cheat::*;
+use gbmu::debug::*;
+use gbmu::disp::*;
+use gbmu::header::*;
+use gbmu::mem::*;
+use gbmu::ops::imp::{dec_rr, rst};
+use gbmu::ops::ops::*;
+use gbmu::reg::{api::*, *};
+use gbmu::sound::*;
+use gbmu::timer::*;
+use gbmu::utils::*;
 use std::env;
 use std::path::Path;
-use timer::*;
-use utils::*;
 
 const DEBUG: bool = true;
 
@@ -52,6 +40,10 @@ fn handl_int(m: &mut Mem, r: &mut Regs) {
                 4 => rst(m, &mut r.sp, &mut r.pc, 0x60),
                 _ => true,
             };
+            if let Some(f) = m.calls.last_mut() {
+                f.int = true;
+            }
+            m.int_evt = Some(i);
             break;
         }
     }
@@ -64,14 +56,29 @@ fn main() {
     let mut disp = Display::new();
     let mut header: Header;
 
-    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let script = match args.iter().position(|a| a == "--debug-script") {
+        Some(i) if i + 1 < args.len() => {
+            args.remove(i);
+            Some(args.remove(i))
+        }
+        Some(_) => fatal_err("Need a script file after --debug-script", 1),
+        None => None,
+    };
     if args.len() != 1 {
         fatal_err("Need a rom file as argument", 1);
     }
+    dbg.load_syms(&args[0]);
+    dbg.load_rc(&args[0]);
+    if let Some(script) = script {
+        dbg.batch(&script);
+    }
+    let mut chts = Cheats::for_rom(&args[0]);
 
     loop {
         let mut mem = Mem::new(&args[0]);
         mem.init_spe_reg();
+        mem.chts = chts;
         let mut audio = Audio::new(mem.snd_data.clone());
 
         if reset {
@@ -94,6 +101,7 @@ fn main() {
         let mut param: u16;
         let mut tmp: u16;
         let mut cycles: usize;
+        let mut pc: u16;
         let mut boot_rom = true;
         let mut halt_bug = false;
         loop {
@@ -102,6 +110,7 @@ fn main() {
                     fatal_err("Can't disable bootrom", 20);
                 }
                 boot_rom = false;
+                mem.boot = false;
             }
             if ((mem.su_get(IE) & 0x1f) & (mem.su_get(IF) & 0x1f)) != 0 && regs.halt {
                 if grr(&regs.ime) == 0 {
@@ -109,20 +118,54 @@ fn main() {
                 }
                 regs.halt = false;
             }
+            let halt = regs.halt;
             if grr(&regs.ime) == 1 && ((mem.su_get(IE) & 0x1f) & (mem.su_get(IF) & 0x1f)) != 0 {
                 handl_int(&mut mem, &mut regs);
+                pc = grr(&regs.pc);
                 cycles = 20;
             } else {
+                pc = grr(&regs.pc);
                 if !regs.halt {
                     opcode = read_opcode(&mem, &mut regs.pc);
                     op = &ops.get(opcode).unwrap_or_else(|| {
                         fatal_err(&format!("Unknown opcode 0x{:02x}", opcode.0), 3)
                     });
                     param = read_param(&mem, &mut regs.pc, op.len());
+                    if !boot_rom {
+                        mem.cdl_exec(pc, op.len());
+                    }
+                    if dbg.snap_due() {
+                        let snap = Snap {
+                            icnt: 0,
+                            mem: mem.snap(),
+                            regs: regs.clone(),
+                            timer: timer.clone(),
+                            disp: disp.snap(),
+                            audio: audio.snap(),
+                            halt_bug,
+                            boot_rom,
+                        };
+                        dbg.push_snap(&mut mem, snap);
+                    }
                     if !dbg.run(&mut mem, &mut regs, op, param) {
                         reset = true;
                         break;
                     }
+                    if let Some(snap) = dbg.rewind() {
+                        match mem.restore(&snap.mem) {
+                            Ok(()) => {
+                                regs = snap.regs.clone();
+                                timer = snap.timer.clone();
+                                disp.restore(&snap.disp);
+                                audio.restore(&snap.audio);
+                                halt_bug = snap.halt_bug;
+                                boot_rom = snap.boot_rom;
+                                mem.boot = boot_rom;
+                                continue;
+                            }
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    }
                     tmp = grr(&regs.pc).wrapping_add(op.len().wrapping_sub(if halt_bug {
                         halt_bug = false;
                         1
@@ -142,9 +185,32 @@ fn main() {
                     cycles = 4;
                 }
             }
+            dbg.prof(&mem, pc, halt, cycles);
             timer.update(&mut mem, cycles);
+            mem.tick(cycles);
             disp.update(&mut mem, cycles);
             audio.update();
         }
+        chts = std::mem::replace(&mut mem.chts, Cheats::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int() {
+        let mut m = Mem::new("");
+        let mut r = Regs::new();
+
+        srr(&mut r.pc, 0xc000);
+        srr(&mut r.sp, 0xdff0);
+        m.su_set(IE, 0x06);
+        m.su_set(IF, 0x05);
+        handl_int(&mut m, &mut r);
+        assert_eq!(m.int_evt, Some(2));
+        assert_eq!((grr(&r.pc), m.su_get(IF) & 0x1f), (0x50, 0x01));
+        assert!(m.calls.last().is_some_and(|f| f.int));
     }
 }
@@ -2,7 +2,7 @@ use crate::header::*;
 use crate::utils::*;
 use chrono::prelude::*;
 use std::fs::{read, write, File};
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
 #[allow(unused_variables)]
@@ -18,9 +18,44 @@ pub trait MBC {
     fn set(&mut self, addr: u16, val: u8) -> Option<()> {
         None
     }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn rom(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        None
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {}
+
+    fn rumble(&self) -> Option<bool> {
+        None
+    }
+
+    fn tilt(&mut self, x: i16, y: i16) {}
+
+    fn tick(&mut self, cycles: usize) {}
+
+    fn snap(&self) -> Vec<u8> {
+        vec![]
+    }
+
+    fn restore(&mut self, snap: &[u8]) {}
 }
 
 fn save(name: &str, ram: &[u8]) {
+    if name.is_empty() {
+        return;
+    }
     write(name, ram).unwrap_or_else(|_| fatal_err("Can't write to backup file", 888));
     println!("Successful backup...");
 }
@@ -35,6 +70,36 @@ fn load(name: &Path, ram: &mut Vec<u8>) -> Option<()> {
     None
 }
 
+fn sav_name(path: &Path) -> String {
+    format!(
+        "../save/{}.sav",
+        path.file_stem()
+            .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
+            .to_str()
+            .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
+    )
+}
+
+fn read_rom(path: &Path) -> Vec<u8> {
+    let mut result = vec![];
+    let mut file = File::open(path).unwrap_or_else(|_| fatal_err("Can't open rom", 99));
+
+    file.read_to_end(&mut result)
+        .unwrap_or_else(|_| fatal_err("Can't read from rom", 102));
+    result
+}
+
+pub fn is_mmm01(path: &Path) -> bool {
+    let mut typ = [0u8];
+
+    File::open(path).is_ok_and(|mut file| {
+        file.metadata().is_ok_and(|md| md.len() >= 0x10000)
+            && file.seek(SeekFrom::End(0x147 - 0x8000)).is_ok()
+            && file.read_exact(&mut typ).is_ok()
+            && matches!(typ[0], 0x0b..=0x0d)
+    })
+}
+
 pub struct MBC0();
 
 impl Drop for MBC0 {
@@ -51,13 +116,13 @@ impl MBC for MBC0 {
 pub struct MBC1 {
     sav_name: String,
     rom: Vec<u8>,
-    rom_sz: usize,
     rom_nb: usize,
     ram: Vec<u8>,
     ram_sz: usize,
     ram_nb: usize,
     ram_en: bool,
     adv_mod: bool,
+    multi: bool,
 }
 
 impl Drop for MBC1 {
@@ -66,59 +131,62 @@ impl Drop for MBC1 {
     }
 }
 
-impl MBC for MBC1 {
-    fn new(path: &Path) -> Box<Self> {
+impl MBC1 {
+    fn with_rom(sav_name: String, rom: Vec<u8>) -> Box<MBC1> {
         let mut result = Box::new(MBC1 {
-            sav_name: format!(
-                "../save/{}.sav",
-                path.file_stem()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-                    .to_str()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-            ),
-            rom: vec![],
-            rom_sz: 0,
+            sav_name,
+            ram_sz: rom[0x149] as usize,
+            multi: MBC1::is_multi(&rom),
+            rom,
             rom_nb: 0x01,
             ram: vec![],
-            ram_sz: 0,
             ram_nb: 0x0,
             ram_en: false,
             adv_mod: false,
         });
-        let mut file = File::open(path).unwrap_or_else(|_| fatal_err("Can't open rom", 99));
 
-        file.read_to_end(&mut result.rom)
-            .unwrap_or_else(|_| fatal_err("Can't read from rom", 102));
-        result.rom_sz = result.rom[0x148] as usize;
-        result.ram_sz = result.rom[0x149] as usize;
         if let None = load(Path::new(&result.sav_name), &mut result.ram) {
             result.ram = vec![0; RAM_SZ[result.ram_sz] / 8 * 0x2000];
         }
+        if result.multi {
+            println!("MBC1 multicart detected");
+        }
         result
     }
 
+    fn is_multi(rom: &[u8]) -> bool {
+        rom.len() == 0x100000
+            && (1..4)
+                .filter(|i| rom[i * 0x40000 + 0x104..i * 0x40000 + 0x134] == NIN_LOGO)
+                .count()
+                >= 2
+    }
+
+    fn ram_off(&self, addr: u16) -> usize {
+        addr as usize - 0xa000
+            + if self.ram_sz > 2 && self.adv_mod {
+                self.ram_nb
+            } else {
+                0
+            } * 0x2000
+    }
+}
+
+impl MBC for MBC1 {
+    fn new(path: &Path) -> Box<Self> {
+        MBC1::with_rom(sav_name(path), read_rom(path))
+    }
+
     fn get(&self, addr: u16) -> Option<u8> {
         match addr {
-            0x0000..=0x3fff if self.rom_sz > 4 && self.adv_mod => {
-                Some(self.rom[addr as usize + (self.ram_nb << 5) * 0x4000])
+            0x0000..=0x3fff if !self.adv_mod => None,
+            0x0000..=0x7fff => self
+                .rom
+                .get(self.rom_bank(addr) * 0x4000 + (addr as usize & 0x3fff))
+                .cloned(),
+            0xa000..=0xbfff if self.ram_sz != 0 && self.ram_en => {
+                Some(self.ram.get(self.ram_off(addr)).cloned().unwrap_or(0xff))
             }
-            0x4000..=0x7fff => Some(
-                self.rom[addr as usize - 0x4000
-                    + (if self.rom_sz > 0x4 {
-                        self.ram_nb << 5
-                    } else {
-                        0
-                    } | self.rom_nb)
-                        * 0x4000],
-            ),
-            0xa000..=0xbfff if self.ram_sz != 0 && self.ram_en => Some(
-                self.ram[addr as usize - 0xa000
-                    + if self.ram_sz > 2 && !self.adv_mod {
-                        0
-                    } else {
-                        self.ram_nb
-                    } * 0x2000],
-            ),
             _ => None,
         }
     }
@@ -129,29 +197,75 @@ impl MBC for MBC1 {
                 self.ram_en = val & 0xf == 0xa;
             }
             0x2000..=0x3fff => {
-                self.rom_nb = val as usize & ((0x1 << (self.rom_sz + 1)) - 1);
+                self.rom_nb = val as usize & 0x1f;
                 if self.rom_nb == 0 {
                     self.rom_nb += 1;
                 }
             }
-            0x4000..=0x5fff if self.ram_sz != 0 => {
+            0x4000..=0x5fff => {
                 self.ram_nb = val as usize & 0x3;
-                if self.ram_sz < 3 {
-                    self.ram_nb = 0;
-                }
             }
             0x6000..=0x7fff => {
                 self.adv_mod = val & 0x1 != 0;
             }
             0xa000..=0xbfff if self.ram_sz != 0 => {
-                if self.ram_en {
-                    self.ram[addr as usize - 0xa000 + self.ram_nb * 0x2000] = val;
+                let off = self.ram_off(addr);
+
+                if let (Some(b), true) = (self.ram.get_mut(off), self.ram_en) {
+                    *b = val;
                 }
             }
             _ => return None,
         }
         Some(())
     }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        self.ram
+            .get(bank * 0x2000 + addr as usize - 0xa000)
+            .cloned()
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {
+        if let Some(b) = self.ram.get_mut(bank * 0x2000 + addr as usize - 0xa000) {
+            *b = val;
+        }
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![
+            self.rom_nb as u8,
+            self.ram_nb as u8,
+            self.ram_en as u8,
+            self.adv_mod as u8,
+        ];
+
+        result.extend_from_slice(&self.ram);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.rom_nb = snap[0] as usize;
+        self.ram_nb = snap[1] as usize;
+        self.ram_en = snap[2] != 0;
+        self.adv_mod = snap[3] != 0;
+        self.ram.copy_from_slice(&snap[4..]);
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        let (sh, msk) = if self.multi { (4, 0xf) } else { (5, 0x1f) };
+        let bank = match addr {
+            0x0000..=0x3fff if self.adv_mod => self.ram_nb << sh,
+            0x4000..=0x7fff => self.ram_nb << sh | self.rom_nb & msk,
+            _ => 0,
+        };
+
+        bank % (self.rom.len() / 0x4000).max(1)
+    }
 }
 
 pub struct MBC2 {
@@ -171,22 +285,13 @@ impl Drop for MBC2 {
 impl MBC for MBC2 {
     fn new(path: &Path) -> Box<Self> {
         let mut result = Box::new(MBC2 {
-            sav_name: format!(
-                "../save/{}.sav",
-                path.file_stem()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-                    .to_str()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-            ),
-            rom: vec![],
+            sav_name: sav_name(path),
+            rom: read_rom(path),
             rom_nb: 0x1,
             ram: vec![],
             ram_en: false,
         });
-        let mut file = File::open(path).unwrap_or_else(|_| fatal_err("Can't open rom", 99));
 
-        file.read_to_end(&mut result.rom)
-            .unwrap_or_else(|_| fatal_err("Can't read from rom", 102));
         if let None = load(Path::new(&result.sav_name), &mut result.ram) {
             result.ram = vec![0; 0x200];
         }
@@ -223,8 +328,56 @@ impl MBC for MBC2 {
         }
         Some(())
     }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        (bank == 0).then(|| self.ram[addr as usize & 0x1ff])
+    }
+
+    fn ram_set(&mut self, _bank: usize, addr: u16, val: u8) {
+        self.ram[addr as usize & 0x1ff] = val & 0xf;
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![self.rom_nb as u8, self.ram_en as u8];
+
+        result.extend_from_slice(&self.ram);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.rom_nb = snap[0] as usize;
+        self.ram_en = snap[1] != 0;
+        self.ram.copy_from_slice(&snap[2..]);
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            0
+        } else {
+            self.rom_nb
+        }
+    }
 }
 
+const NIN_LOGO: [u8; 48] = [
+    0xce, 0xed, 0x66, 0x66, 0xcc, 0x0d, 0x00, 0x0b, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0c, 0x00, 0x0d,
+    0x00, 0x08, 0x11, 0x1f, 0x88, 0x89, 0x00, 0x0e, 0xdc, 0xcc, 0x6e, 0xe6, 0xdd, 0xdd, 0xd9, 0x99,
+    0xbb, 0xbb, 0x67, 0x63, 0x6e, 0x0e, 0xec, 0xcc, 0xdd, 0xdc, 0x99, 0x9f, 0xbb, 0xb9, 0x33, 0x3e,
+];
+
+const RTC_SZ: usize = 48;
+const HUC3_SZ: usize = 12;
+const CLK_SZ: usize = 16;
+const CLK_HZ: usize = 0x400000;
+const EEP_SZ: usize = 0x100;
+const ACC_MID: u16 = 0x81d0;
+const RTC_MSK: [u8; 5] = [0x3f, 0x3f, 0x1f, 0xff, 0xc1];
+
+#[derive(Clone, Copy)]
 enum RamClk {
     RAM,
     S,
@@ -244,26 +397,24 @@ pub struct MBC3 {
     ram_en: bool,
     lat_clk: bool,
     ram_clk: RamClk,
-    loc_tm: DateTime<Local>,
+    rtc: Option<Rtc>,
+    clk: Clk,
 }
 
 impl Drop for MBC3 {
     fn drop(&mut self) {
-        save(&self.sav_name, &self.ram);
+        match &self.rtc {
+            Some(rtc) => save(&self.sav_name, &[&self.ram[..], &rtc.footer()].concat()),
+            None => save(&self.sav_name, &self.ram),
+        }
     }
 }
 
 impl MBC for MBC3 {
     fn new(path: &Path) -> Box<Self> {
         let mut result = Box::new(MBC3 {
-            sav_name: format!(
-                "../save/{}.sav",
-                path.file_stem()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-                    .to_str()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-            ),
-            rom: vec![],
+            sav_name: sav_name(path),
+            rom: read_rom(path),
             rom_nb: 0x01,
             ram: vec![],
             ram_sz: 0,
@@ -271,16 +422,23 @@ impl MBC for MBC3 {
             ram_en: false,
             lat_clk: true,
             ram_clk: RamClk::RAM,
-            loc_tm: Local::now(),
+            rtc: None,
+            clk: Clk::new(Local::now().timestamp()),
         });
-        let mut file = File::open(path).unwrap_or_else(|_| fatal_err("Can't open rom", 99));
 
-        file.read_to_end(&mut result.rom)
-            .unwrap_or_else(|_| fatal_err("Can't read from rom", 102));
         result.ram_sz = result.rom[0x149] as usize;
+        let ram_len = RAM_SZ[result.ram_sz] / 8 * 0x2000;
+        let now = result.clk.secs;
+
         if let None = load(Path::new(&result.sav_name), &mut result.ram) {
-            result.ram = vec![0; RAM_SZ[result.ram_sz] / 8 * 0x2000];
+            result.ram = vec![0; ram_len];
+        }
+        if matches!(result.rom[0x147], 0x0f | 0x10) {
+            let ftr = result.ram.split_off(ram_len.min(result.ram.len()));
+
+            result.rtc = Some(Rtc::parse(&ftr).unwrap_or_else(|| Rtc::new(now)));
         }
+        result.ram.resize(ram_len, 0);
         result
     }
 
@@ -295,11 +453,7 @@ impl MBC for MBC3 {
                         Some(self.ram[addr as usize - 0xa000 + self.ram_nb * 0x2000])
                     }
                 }
-                RamClk::S => Some(self.now(RamClk::S)),
-                RamClk::M => Some(self.now(RamClk::M)),
-                RamClk::H => Some(self.now(RamClk::H)),
-                RamClk::DL => Some(self.now(RamClk::DL)),
-                RamClk::DH => Some(self.now(RamClk::DH)),
+                clk => Some(self.rtc.as_ref().map_or(0xff, |rtc| rtc.get(clk))),
             },
             _ => None,
         }
@@ -337,36 +491,218 @@ impl MBC for MBC3 {
                 0x00 if self.lat_clk => self.lat_clk = false,
                 0x01 if !self.lat_clk => {
                     self.lat_clk = true;
-                    self.loc_tm = Local::now();
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.latch(self.clk.secs);
+                    }
                 }
                 _ => (),
             },
-            0xa000..=0xbfff => {
-                if let RamClk::RAM = self.ram_clk {
+            0xa000..=0xbfff => match self.ram_clk {
+                RamClk::RAM => {
                     if self.ram_sz == 0 {
                         return None;
                     } else if self.ram_en {
                         self.ram[addr as usize - 0xa000 + self.ram_nb * 0x2000] = val;
                     }
                 }
-            }
+                clk => {
+                    if let (Some(rtc), true) = (&mut self.rtc, self.ram_en) {
+                        rtc.set(clk, val, self.clk.secs);
+                    }
+                }
+            },
             _ => return None,
         }
         Some(())
     }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        self.ram
+            .get(bank * 0x2000 + addr as usize - 0xa000)
+            .cloned()
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {
+        if let Some(b) = self.ram.get_mut(bank * 0x2000 + addr as usize - 0xa000) {
+            *b = val;
+        }
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        self.clk.tick(cycles);
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![
+            self.rom_nb as u8,
+            self.ram_nb as u8,
+            self.ram_en as u8,
+            self.lat_clk as u8,
+            self.ram_clk as u8,
+        ];
+
+        result.extend_from_slice(&self.clk.snap());
+        if let Some(rtc) = &self.rtc {
+            result.extend_from_slice(&rtc.footer());
+        }
+        result.extend_from_slice(&self.ram);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.rom_nb = snap[0] as usize;
+        self.ram_nb = snap[1] as usize;
+        self.ram_en = snap[2] != 0;
+        self.lat_clk = snap[3] != 0;
+        self.ram_clk = match snap[4] {
+            1 => RamClk::S,
+            2 => RamClk::M,
+            3 => RamClk::H,
+            4 => RamClk::DL,
+            5 => RamClk::DH,
+            _ => RamClk::RAM,
+        };
+        self.clk = Clk::parse(&snap[5..5 + CLK_SZ]);
+        let mut snap = &snap[5 + CLK_SZ..];
+
+        if let Some(rtc) = &mut self.rtc {
+            *rtc = Rtc::parse(&snap[..RTC_SZ]).unwrap_or_else(|| rtc.clone());
+            snap = &snap[RTC_SZ..];
+        }
+        self.ram.copy_from_slice(snap);
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            0
+        } else {
+            self.rom_nb
+        }
+    }
 }
 
-impl MBC3 {
-    fn now(&self, typ: RamClk) -> u8 {
-        match typ {
-            RamClk::S => self.loc_tm.second() as u8,
-            RamClk::M => self.loc_tm.minute() as u8,
-            RamClk::H => self.loc_tm.hour() as u8,
-            RamClk::DL => self.loc_tm.ordinal0() as u8,
-            RamClk::DH => ((self.loc_tm.ordinal0() >> 8) & 0x1) as u8,
-            _ => 0,
+#[derive(Clone, Copy)]
+struct Clk {
+    secs: i64,
+    cyc: usize,
+}
+
+impl Clk {
+    fn new(secs: i64) -> Clk {
+        Clk { secs, cyc: 0 }
+    }
+
+    fn parse(snap: &[u8]) -> Clk {
+        let word = |i: usize| {
+            let mut b = [0; 8];
+
+            b.copy_from_slice(&snap[i..i + 8]);
+            i64::from_le_bytes(b)
+        };
+
+        Clk {
+            secs: word(0),
+            cyc: word(8) as usize,
         }
     }
+
+    fn snap(&self) -> Vec<u8> {
+        [self.secs.to_le_bytes(), (self.cyc as i64).to_le_bytes()].concat()
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        self.cyc += cycles;
+        if self.cyc >= CLK_HZ {
+            self.secs += (self.cyc / CLK_HZ) as i64;
+            self.cyc %= CLK_HZ;
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Rtc {
+    regs: [u8; 5],
+    lat: [u8; 5],
+    base: i64,
+}
+
+impl Rtc {
+    fn new(now: i64) -> Rtc {
+        Rtc {
+            regs: [0; 5],
+            lat: [0; 5],
+            base: now,
+        }
+    }
+
+    fn parse(ftr: &[u8]) -> Option<Rtc> {
+        let word = |i: usize| u32::from_le_bytes([ftr[i], ftr[i + 1], ftr[i + 2], ftr[i + 3]]);
+        let mut result = Rtc::new(match ftr.len() {
+            RTC_SZ => (word(44) as i64) << 32 | word(40) as i64,
+            44 => word(40) as i64,
+            _ => return None,
+        });
+
+        for (i, msk) in RTC_MSK.iter().enumerate() {
+            result.regs[i] = word(i * 4) as u8 & msk;
+            result.lat[i] = word(20 + i * 4) as u8 & msk;
+        }
+        Some(result)
+    }
+
+    fn footer(&self) -> Vec<u8> {
+        self.regs
+            .iter()
+            .chain(self.lat.iter())
+            .flat_map(|r| (*r as u32).to_le_bytes())
+            .chain(self.base.to_le_bytes())
+            .collect()
+    }
+
+    fn sync(&mut self, now: i64) {
+        let elapsed = now - self.base;
+
+        self.base = now;
+        if self.regs[4] & 0x40 != 0 || elapsed <= 0 {
+            return;
+        }
+        let days = (self.regs[4] as i64 & 0x1) << 8 | self.regs[3] as i64;
+        let secs = self.regs[0] as i64
+            + self.regs[1] as i64 * 60
+            + self.regs[2] as i64 * 3600
+            + days * 86400
+            + elapsed;
+        let days = secs / 86400;
+
+        self.regs[0] = (secs % 60) as u8;
+        self.regs[1] = (secs / 60 % 60) as u8;
+        self.regs[2] = (secs / 3600 % 24) as u8;
+        self.regs[3] = days as u8;
+        self.regs[4] = self.regs[4] & 0xc0 | (days >> 8 & 0x1) as u8;
+        if days > 0x1ff {
+            self.regs[4] |= 0x80;
+        }
+    }
+
+    fn latch(&mut self, now: i64) {
+        self.sync(now);
+        self.lat = self.regs;
+    }
+
+    fn get(&self, clk: RamClk) -> u8 {
+        self.lat[clk as usize - 1]
+    }
+
+    fn set(&mut self, clk: RamClk, val: u8, now: i64) {
+        let idx = clk as usize - 1;
+
+        self.sync(now);
+        self.regs[idx] = val & RTC_MSK[idx];
+    }
 }
 
 pub struct MBC5 {
@@ -377,6 +713,7 @@ pub struct MBC5 {
     ram_sz: usize,
     ram_nb: usize,
     ram_en: bool,
+    rmbl: Option<bool>,
 }
 
 impl Drop for MBC5 {
@@ -385,40 +722,42 @@ impl Drop for MBC5 {
     }
 }
 
-impl MBC for MBC5 {
-    fn new(path: &Path) -> Box<Self> {
+impl MBC5 {
+    fn with_rom(sav_name: String, rom: Vec<u8>) -> Box<MBC5> {
         let mut result = Box::new(MBC5 {
-            sav_name: format!(
-                "../save/{}.sav",
-                path.file_stem()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-                    .to_str()
-                    .unwrap_or_else(|| fatal_err("Bad backup file name", 218))
-            ),
-            rom: vec![],
+            sav_name,
+            ram_sz: rom[0x149] as usize,
+            rmbl: matches!(rom[0x147], 0x1c..=0x1e).then_some(false),
+            rom,
             rom_nb: 0x01,
             ram: vec![],
-            ram_sz: 0,
             ram_nb: 0x0,
             ram_en: false,
         });
-        let mut file = File::open(path).unwrap_or_else(|_| fatal_err("Can't open rom", 99));
 
-        file.read_to_end(&mut result.rom)
-            .unwrap_or_else(|_| fatal_err("Can't read from rom", 102));
-        result.ram_sz = result.rom[0x149] as usize;
         if let None = load(Path::new(&result.sav_name), &mut result.ram) {
             result.ram = vec![0; RAM_SZ[result.ram_sz] / 8 * 0x2000];
         }
         result
     }
+}
+
+impl MBC for MBC5 {
+    fn new(path: &Path) -> Box<Self> {
+        MBC5::with_rom(sav_name(path), read_rom(path))
+    }
 
     fn get(&self, addr: u16) -> Option<u8> {
         match addr {
-            0x4000..=0x7fff => Some(self.rom[addr as usize - 0x4000 + self.rom_nb * 0x4000]),
-            0xa000..=0xbfff if self.ram_sz != 0 && self.ram_en => {
-                Some(self.ram[addr as usize - 0xa000 + self.ram_nb * 0x2000])
+            0x4000..=0x7fff => {
+                Some(self.rom[addr as usize - 0x4000 + self.rom_bank(addr) * 0x4000])
             }
+            0xa000..=0xbfff if self.ram_sz != 0 && self.ram_en => Some(
+                self.ram
+                    .get(addr as usize - 0xa000 + self.ram_nb * 0x2000)
+                    .cloned()
+                    .unwrap_or(0xff),
+            ),
             _ => None,
         }
     }
@@ -434,16 +773,1318 @@ impl MBC for MBC5 {
             0x3000..=0x3fff => {
                 self.rom_nb = (self.rom_nb & 0xff) | (val as usize & 0x1 << 8);
             }
-            0x4000..=0x5fff if self.ram_sz != 0 => {
-                self.ram_nb = val as usize;
+            0x4000..=0x5fff if self.ram_sz != 0 || self.rmbl.is_some() => {
+                let banks = (self.ram.len() / 0x2000).max(1);
+
+                if self.rmbl.is_some() {
+                    self.rmbl = Some(val & 0x08 != 0);
+                    self.ram_nb = (val as usize & 0x07) % banks;
+                } else {
+                    self.ram_nb = (val as usize & 0x0f) % banks;
+                }
             }
             0xa000..=0xbfff if self.ram_sz != 0 => {
-                if self.ram_en {
-                    self.ram[addr as usize - 0xa000 + self.ram_nb * 0x2000] = val;
+                if let (Some(b), true) = (
+                    self.ram
+                        .get_mut(addr as usize - 0xa000 + self.ram_nb * 0x2000),
+                    self.ram_en,
+                ) {
+                    *b = val;
                 }
             }
             _ => return None,
         }
         Some(())
     }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        self.ram
+            .get(bank * 0x2000 + addr as usize - 0xa000)
+            .cloned()
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {
+        if let Some(b) = self.ram.get_mut(bank * 0x2000 + addr as usize - 0xa000) {
+            *b = val;
+        }
+    }
+
+    fn rumble(&self) -> Option<bool> {
+        self.rmbl
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![
+            self.rom_nb as u8,
+            (self.rom_nb >> 8) as u8,
+            self.ram_nb as u8,
+            self.ram_en as u8,
+            self.rmbl.map_or(0, |on| 1 + on as u8),
+        ];
+
+        result.extend_from_slice(&self.ram);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.rom_nb = snap[0] as usize | (snap[1] as usize) << 8;
+        self.ram_nb = snap[2] as usize;
+        self.ram_en = snap[3] != 0;
+        if self.rmbl.is_some() {
+            self.rmbl = Some(snap[4] == 2);
+        }
+        self.ram.copy_from_slice(&snap[5..]);
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            0
+        } else {
+            self.rom_nb % (self.rom.len() / 0x4000).max(1)
+        }
+    }
+}
+
+pub struct RomRam {
+    sav_name: String,
+    ram: Vec<u8>,
+}
+
+impl Drop for RomRam {
+    fn drop(&mut self) {
+        save(&self.sav_name, &self.ram);
+    }
+}
+
+impl RomRam {
+    fn with_rom(sav_name: String, rom: &[u8]) -> Box<RomRam> {
+        let mut result = Box::new(RomRam {
+            sav_name,
+            ram: vec![],
+        });
+        let ram_len = (RAM_SZ.get(rom[0x149] as usize).cloned().unwrap_or(0) * 0x400).min(0x2000);
+
+        if load(Path::new(&result.sav_name), &mut result.ram).is_none() {
+            result.ram = vec![0; ram_len];
+        }
+        result.ram.resize(ram_len, 0);
+        result
+    }
+}
+
+impl MBC for RomRam {
+    fn new(path: &Path) -> Box<Self> {
+        let rom = read_rom(path);
+
+        RomRam::with_rom(
+            if rom[0x147] == 0x09 {
+                sav_name(path)
+            } else {
+                String::new()
+            },
+            &rom,
+        )
+    }
+
+    fn get(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0xa000..=0xbfff if !self.ram.is_empty() => {
+                Some(self.ram[(addr as usize - 0xa000) % self.ram.len()])
+            }
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, addr: u16, val: u8) -> Option<()> {
+        match addr {
+            0x0000..=0x7fff => (),
+            0xa000..=0xbfff if !self.ram.is_empty() => {
+                let len = self.ram.len();
+
+                self.ram[(addr as usize - 0xa000) % len] = val;
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        match bank {
+            0 => self.ram.get(addr as usize - 0xa000).cloned(),
+            _ => None,
+        }
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {
+        if let (Some(b), 0) = (self.ram.get_mut(addr as usize - 0xa000), bank) {
+            *b = val;
+        }
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.ram.copy_from_slice(snap);
+    }
+}
+
+pub struct MMM01 {
+    sav_name: String,
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_en: bool,
+    lock: bool,
+    rom_lo: usize,
+    rom_mid: usize,
+    rom_hi: usize,
+    rom_msk: usize,
+    ram_lo: usize,
+    ram_hi: usize,
+    ram_msk: usize,
+    mode: bool,
+    mode_dis: bool,
+    mux: bool,
+}
+
+impl Drop for MMM01 {
+    fn drop(&mut self) {
+        save(&self.sav_name, &self.ram);
+    }
+}
+
+impl MMM01 {
+    fn with_rom(sav_name: String, mut rom: Vec<u8>) -> Box<MMM01> {
+        if rom.len() < 0x8000 {
+            rom.resize(0x8000, 0xff);
+        }
+        let ram_sz = rom[rom.len() - 0x8000 + 0x149] as usize;
+        let mut result = Box::new(MMM01 {
+            sav_name,
+            rom,
+            ram: vec![],
+            ram_en: false,
+            lock: false,
+            rom_lo: 0,
+            rom_mid: 0,
+            rom_hi: 0,
+            rom_msk: 0,
+            ram_lo: 0,
+            ram_hi: 0,
+            ram_msk: 0,
+            mode: false,
+            mode_dis: false,
+            mux: false,
+        });
+        let ram_len = RAM_SZ.get(ram_sz).cloned().unwrap_or(0) * 0x400;
+
+        if load(Path::new(&result.sav_name), &mut result.ram).is_none() {
+            result.ram = vec![0; ram_len];
+        }
+        result.ram.resize(ram_len, 0);
+        result
+    }
+
+    fn banks(&self) -> (usize, usize) {
+        let nb = (self.rom.len() / 0x4000).max(1);
+
+        if !self.lock {
+            return ((nb * 2 - 2) % nb, nb - 1);
+        }
+        let (mid0, mid) = if self.mux {
+            (if self.mode { 0 } else { self.ram_lo }, self.ram_lo)
+        } else {
+            (self.rom_mid, self.rom_mid)
+        };
+        let rom0 = self.rom_lo & (self.rom_msk << 1) | mid0 << 5 | self.rom_hi << 7;
+        let mut rom = self.rom_lo | mid << 5 | self.rom_hi << 7;
+
+        if rom == rom0 {
+            rom += 1;
+        }
+        (rom0 % nb, rom % nb)
+    }
+
+    fn ram_off(&self, addr: u16) -> Option<usize> {
+        let bank = if self.mux {
+            self.rom_mid | self.ram_hi << 2
+        } else {
+            self.ram_lo | self.ram_hi << 2
+        };
+
+        match self.ram.len() {
+            0 => None,
+            len => Some((bank * 0x2000 + addr as usize - 0xa000) % len),
+        }
+    }
+}
+
+impl MBC for MMM01 {
+    fn new(path: &Path) -> Box<Self> {
+        let rom = read_rom(path);
+        let typ = rom.get(rom.len().saturating_sub(0x8000) + 0x147).cloned();
+
+        MMM01::with_rom(
+            if typ == Some(0x0d) {
+                sav_name(path)
+            } else {
+                String::new()
+            },
+            rom,
+        )
+    }
+
+    fn get(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x0000..=0x7fff => self
+                .rom
+                .get(self.rom_bank(addr) * 0x4000 + (addr as usize & 0x3fff))
+                .cloned(),
+            0xa000..=0xbfff if self.ram_en => self.ram_off(addr).map(|off| self.ram[off]),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, addr: u16, val: u8) -> Option<()> {
+        let val = val as usize;
+
+        match addr {
+            0x0000..=0x1fff => {
+                self.ram_en = val & 0xf == 0xa;
+                if !self.lock {
+                    self.ram_msk = val >> 4 & 0x3;
+                    self.lock = val & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3fff => {
+                let msk = self.rom_msk << 1;
+
+                if !self.lock {
+                    self.rom_mid = val >> 5 & 0x3;
+                }
+                self.rom_lo = self.rom_lo & msk | val & 0x1f & !msk;
+            }
+            0x4000..=0x5fff => {
+                self.ram_lo = self.ram_lo & self.ram_msk | val & 0x3 & !self.ram_msk;
+                if !self.lock {
+                    self.ram_hi = val >> 2 & 0x3;
+                    self.rom_hi = val >> 4 & 0x3;
+                    self.mode_dis = val & 0x40 != 0;
+                }
+            }
+            0x6000..=0x7fff => {
+                if !self.mode_dis {
+                    self.mode = val & 0x1 != 0;
+                }
+                if !self.lock {
+                    self.rom_msk = val >> 2 & 0xf;
+                    self.mux = val & 0x40 != 0;
+                }
+            }
+            0xa000..=0xbfff => {
+                if let (Some(off), true) = (self.ram_off(addr), self.ram_en) {
+                    self.ram[off] = val as u8;
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        self.ram
+            .get(bank * 0x2000 + addr as usize - 0xa000)
+            .cloned()
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {
+        if let Some(b) = self.ram.get_mut(bank * 0x2000 + addr as usize - 0xa000) {
+            *b = val;
+        }
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![
+            self.ram_en as u8,
+            self.lock as u8,
+            self.rom_lo as u8,
+            self.rom_mid as u8,
+            self.rom_hi as u8,
+            self.rom_msk as u8,
+            self.ram_lo as u8,
+            self.ram_hi as u8,
+            self.ram_msk as u8,
+            self.mode as u8,
+            self.mode_dis as u8,
+            self.mux as u8,
+        ];
+
+        result.extend_from_slice(&self.ram);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.ram_en = snap[0] != 0;
+        self.lock = snap[1] != 0;
+        self.rom_lo = snap[2] as usize;
+        self.rom_mid = snap[3] as usize;
+        self.rom_hi = snap[4] as usize;
+        self.rom_msk = snap[5] as usize;
+        self.ram_lo = snap[6] as usize;
+        self.ram_hi = snap[7] as usize;
+        self.ram_msk = snap[8] as usize;
+        self.mode = snap[9] != 0;
+        self.mode_dis = snap[10] != 0;
+        self.mux = snap[11] != 0;
+        self.ram.copy_from_slice(&snap[12..]);
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        let (rom0, rom) = self.banks();
+
+        if addr < 0x4000 {
+            rom0
+        } else {
+            rom
+        }
+    }
+}
+
+pub struct HuC1 {
+    sav_name: String,
+    rom: Vec<u8>,
+    rom_nb: usize,
+    ram: Vec<u8>,
+    ram_nb: usize,
+    ir: bool,
+    ir_on: bool,
+    light: bool,
+}
+
+impl Drop for HuC1 {
+    fn drop(&mut self) {
+        save(&self.sav_name, &self.ram);
+    }
+}
+
+impl HuC1 {
+    fn with_rom(sav_name: String, rom: Vec<u8>) -> Box<HuC1> {
+        let ram_len = RAM_SZ.get(rom[0x149] as usize).cloned().unwrap_or(0) * 0x400;
+        let mut result = Box::new(HuC1 {
+            sav_name,
+            rom,
+            rom_nb: 0x01,
+            ram: vec![],
+            ram_nb: 0x0,
+            ir: false,
+            ir_on: false,
+            light: false,
+        });
+
+        if load(Path::new(&result.sav_name), &mut result.ram).is_none() {
+            result.ram = vec![0; ram_len];
+        }
+        result.ram.resize(ram_len, 0);
+        result
+    }
+
+    fn ram_off(&self, addr: u16) -> Option<usize> {
+        match self.ram.len() {
+            0 => None,
+            len => Some((self.ram_nb * 0x2000 + addr as usize - 0xa000) % len),
+        }
+    }
+}
+
+impl MBC for HuC1 {
+    fn new(path: &Path) -> Box<Self> {
+        HuC1::with_rom(sav_name(path), read_rom(path))
+    }
+
+    fn get(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4000..=0x7fff => self
+                .rom
+                .get(self.rom_bank(addr) * 0x4000 + addr as usize - 0x4000)
+                .cloned(),
+            0xa000..=0xbfff if self.ir => Some(if self.light { 0xc1 } else { 0xc0 }),
+            0xa000..=0xbfff => self.ram_off(addr).map(|off| self.ram[off]),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, addr: u16, val: u8) -> Option<()> {
+        match addr {
+            0x0000..=0x1fff => {
+                self.ir = val == 0x0e;
+            }
+            0x2000..=0x3fff => {
+                self.rom_nb = val as usize & 0x3f;
+            }
+            0x4000..=0x5fff => {
+                self.ram_nb = val as usize & 0x3;
+            }
+            0x6000..=0x7fff => (),
+            0xa000..=0xbfff if self.ir => {
+                self.ir_on = val & 0x1 != 0;
+            }
+            0xa000..=0xbfff => {
+                if let Some(off) = self.ram_off(addr) {
+                    self.ram[off] = val;
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        self.ram
+            .get(bank * 0x2000 + addr as usize - 0xa000)
+            .cloned()
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {
+        if let Some(b) = self.ram.get_mut(bank * 0x2000 + addr as usize - 0xa000) {
+            *b = val;
+        }
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![
+            self.rom_nb as u8,
+            self.ram_nb as u8,
+            self.ir as u8,
+            self.ir_on as u8,
+        ];
+
+        result.extend_from_slice(&self.ram);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.rom_nb = snap[0] as usize;
+        self.ram_nb = snap[1] as usize;
+        self.ir = snap[2] != 0;
+        self.ir_on = snap[3] != 0;
+        self.ram.copy_from_slice(&snap[4..]);
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            0
+        } else {
+            self.rom_nb.max(1) % (self.rom.len() / 0x4000).max(1)
+        }
+    }
+}
+
+pub struct HuC3 {
+    sav_name: String,
+    rom: Vec<u8>,
+    rom_nb: usize,
+    ram: Vec<u8>,
+    ram_nb: usize,
+    mode: u8,
+    cmd: u8,
+    resp: u8,
+    idx: usize,
+    regs: [u8; 0x100],
+    mins: u16,
+    days: u16,
+    base: i64,
+    clk: Clk,
+}
+
+impl Drop for HuC3 {
+    fn drop(&mut self) {
+        save(&self.sav_name, &[&self.ram[..], &self.footer()].concat());
+    }
+}
+
+impl HuC3 {
+    fn with_rom(sav_name: String, rom: Vec<u8>, now: i64) -> Box<HuC3> {
+        let ram_len = RAM_SZ.get(rom[0x149] as usize).cloned().unwrap_or(0) * 0x400;
+        let mut result = Box::new(HuC3 {
+            sav_name,
+            rom,
+            rom_nb: 0x01,
+            ram: vec![],
+            ram_nb: 0x0,
+            mode: 0,
+            cmd: 0,
+            resp: 0,
+            idx: 0,
+            regs: [0; 0x100],
+            mins: 0,
+            days: 0,
+            base: now,
+            clk: Clk::new(now),
+        });
+
+        if load(Path::new(&result.sav_name), &mut result.ram).is_none() {
+            result.ram = vec![0; ram_len];
+        }
+        let ftr = result.ram.split_off(ram_len.min(result.ram.len()));
+
+        if ftr.len() == HUC3_SZ {
+            result.base = i64::from_le_bytes([
+                ftr[0], ftr[1], ftr[2], ftr[3], ftr[4], ftr[5], ftr[6], ftr[7],
+            ]);
+            result.mins = u16::from_le_bytes([ftr[8], ftr[9]]);
+            result.days = u16::from_le_bytes([ftr[10], ftr[11]]);
+        }
+        result.ram.resize(ram_len, 0);
+        result
+    }
+
+    fn footer(&self) -> Vec<u8> {
+        [
+            &self.base.to_le_bytes()[..],
+            &self.mins.to_le_bytes(),
+            &self.days.to_le_bytes(),
+        ]
+        .concat()
+    }
+
+    fn sync(&mut self, now: i64) {
+        let elapsed = (now - self.base).max(0) / 60;
+        let mins = self.mins as i64 + elapsed;
+
+        self.base += elapsed * 60;
+        self.mins = (mins % 1440) as u16;
+        self.days = ((self.days as i64 + mins / 1440) & 0xfff) as u16;
+    }
+
+    fn exec(&mut self, val: u8, now: i64) {
+        let arg = val & 0xf;
+
+        self.cmd = val & 0x70;
+        match val >> 4 & 0x7 {
+            0x1 => {
+                self.resp = self.regs[self.idx];
+                self.idx = (self.idx + 1) & 0xff;
+            }
+            0x3 => {
+                self.regs[self.idx] = arg;
+                self.idx = (self.idx + 1) & 0xff;
+            }
+            0x4 => self.idx = self.idx & 0xf0 | arg as usize,
+            0x5 => self.idx = self.idx & 0x0f | (arg as usize) << 4,
+            0x6 => match arg {
+                0x0 => {
+                    self.sync(now);
+                    for i in 0..3 {
+                        self.regs[i] = (self.mins >> (i * 4)) as u8 & 0xf;
+                        self.regs[i + 3] = (self.days >> (i * 4)) as u8 & 0xf;
+                    }
+                }
+                0x1 => {
+                    let regs = self.regs;
+                    let nib = |i: usize| (regs[i] as u16) << ((i % 3) * 4);
+
+                    self.mins = (nib(0) | nib(1) | nib(2)) % 1440;
+                    self.days = nib(3) | nib(4) | nib(5);
+                    self.base = now;
+                }
+                0x2 => self.resp = 0x1,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    fn ram_off(&self, addr: u16) -> Option<usize> {
+        match self.ram.len() {
+            0 => None,
+            len => Some((self.ram_nb * 0x2000 + addr as usize - 0xa000) % len),
+        }
+    }
+}
+
+impl MBC for HuC3 {
+    fn new(path: &Path) -> Box<Self> {
+        HuC3::with_rom(sav_name(path), read_rom(path), Local::now().timestamp())
+    }
+
+    fn get(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4000..=0x7fff => self
+                .rom
+                .get(self.rom_bank(addr) * 0x4000 + addr as usize - 0x4000)
+                .cloned(),
+            0xa000..=0xbfff => match self.mode {
+                0x0 | 0xa => self.ram_off(addr).map(|off| self.ram[off]),
+                0xc => Some(0x80 | self.cmd | self.resp),
+                0xd => Some(0x01),
+                0xe => Some(0xc0),
+                _ => Some(0xff),
+            },
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, addr: u16, val: u8) -> Option<()> {
+        match addr {
+            0x0000..=0x1fff => {
+                self.mode = val & 0xf;
+            }
+            0x2000..=0x3fff => {
+                self.rom_nb = val as usize & 0x7f;
+            }
+            0x4000..=0x5fff => {
+                self.ram_nb = val as usize & 0x3;
+            }
+            0x6000..=0x7fff => (),
+            0xa000..=0xbfff => match self.mode {
+                0xa => {
+                    if let Some(off) = self.ram_off(addr) {
+                        self.ram[off] = val;
+                    }
+                }
+                0xb => self.exec(val, self.clk.secs),
+                _ => (),
+            },
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn ram_get(&self, bank: usize, addr: u16) -> Option<u8> {
+        self.ram
+            .get(bank * 0x2000 + addr as usize - 0xa000)
+            .cloned()
+    }
+
+    fn ram_set(&mut self, bank: usize, addr: u16, val: u8) {
+        if let Some(b) = self.ram.get_mut(bank * 0x2000 + addr as usize - 0xa000) {
+            *b = val;
+        }
+    }
+
+    fn tick(&mut self, cycles: usize) {
+        self.clk.tick(cycles);
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![
+            self.rom_nb as u8,
+            self.ram_nb as u8,
+            self.mode,
+            self.cmd,
+            self.resp,
+            self.idx as u8,
+        ];
+
+        result.extend_from_slice(&self.regs);
+        result.extend_from_slice(&self.footer());
+        result.extend_from_slice(&self.clk.snap());
+        result.extend_from_slice(&self.ram);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        let ftr = &snap[6 + 0x100..6 + 0x100 + HUC3_SZ];
+
+        self.rom_nb = snap[0] as usize;
+        self.ram_nb = snap[1] as usize;
+        self.mode = snap[2];
+        self.cmd = snap[3];
+        self.resp = snap[4];
+        self.idx = snap[5] as usize;
+        self.regs.copy_from_slice(&snap[6..6 + 0x100]);
+        self.base = i64::from_le_bytes([
+            ftr[0], ftr[1], ftr[2], ftr[3], ftr[4], ftr[5], ftr[6], ftr[7],
+        ]);
+        self.mins = u16::from_le_bytes([ftr[8], ftr[9]]);
+        self.days = u16::from_le_bytes([ftr[10], ftr[11]]);
+        self.clk = Clk::parse(&snap[6 + 0x100 + HUC3_SZ..]);
+        self.ram
+            .copy_from_slice(&snap[6 + 0x100 + HUC3_SZ + CLK_SZ..]);
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            0
+        } else {
+            self.rom_nb.max(1) % (self.rom.len() / 0x4000).max(1)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum EepSt {
+    Idle,
+    Cmd(u16, u8),
+    Read(u16, u8),
+    Write(Option<usize>, u16, u8),
+}
+
+struct Eep {
+    data: Vec<u8>,
+    st: EepSt,
+    wen: bool,
+    cs: bool,
+    clk: bool,
+    di: bool,
+    dout: bool,
+}
+
+impl Eep {
+    fn new(data: Vec<u8>) -> Eep {
+        Eep {
+            data,
+            st: EepSt::Idle,
+            wen: false,
+            cs: false,
+            clk: false,
+            di: false,
+            dout: true,
+        }
+    }
+
+    fn word(&self, addr: usize) -> u16 {
+        u16::from_le_bytes([self.data[addr * 2], self.data[addr * 2 + 1]])
+    }
+
+    fn word_set(&mut self, addr: usize, val: u16) {
+        if self.wen {
+            self.data[addr * 2..addr * 2 + 2].copy_from_slice(&val.to_le_bytes());
+        }
+    }
+
+    fn get(&self) -> u8 {
+        (self.cs as u8) << 7 | (self.clk as u8) << 6 | (self.di as u8) << 1 | self.dout as u8
+    }
+
+    fn set(&mut self, val: u8) {
+        let (cs, clk, di) = (val & 0x80 != 0, val & 0x40 != 0, val & 0x02 != 0);
+
+        if !cs {
+            self.st = EepSt::Idle;
+        } else if self.cs && clk && !self.clk {
+            self.st = self.clock(di);
+        }
+        self.cs = cs;
+        self.clk = clk;
+        self.di = di;
+    }
+
+    fn clock(&mut self, di: bool) -> EepSt {
+        match self.st {
+            EepSt::Idle if di => EepSt::Cmd(0, 0),
+            EepSt::Idle => EepSt::Idle,
+            EepSt::Cmd(bits, n) if n < 9 => EepSt::Cmd(bits << 1 | di as u16, n + 1),
+            EepSt::Cmd(bits, _) => self.exec(bits << 1 | di as u16),
+            EepSt::Read(val, n) => {
+                self.dout = val >> (15 - n) & 0x1 != 0;
+                if n < 15 {
+                    EepSt::Read(val, n + 1)
+                } else {
+                    EepSt::Idle
+                }
+            }
+            EepSt::Write(addr, val, n) if n < 15 => EepSt::Write(addr, val << 1 | di as u16, n + 1),
+            EepSt::Write(addr, val, _) => {
+                let val = val << 1 | di as u16;
+
+                match addr {
+                    Some(addr) => self.word_set(addr, val),
+                    None => (0..EEP_SZ / 2).for_each(|a| self.word_set(a, val)),
+                }
+                self.dout = true;
+                EepSt::Idle
+            }
+        }
+    }
+
+    fn exec(&mut self, bits: u16) -> EepSt {
+        let addr = bits as usize & 0x7f;
+
+        match (bits >> 8 & 0x3, bits >> 6 & 0x3) {
+            (0b10, _) => {
+                self.dout = false;
+                return EepSt::Read(self.word(addr), 0);
+            }
+            (0b01, _) => return EepSt::Write(Some(addr), 0, 0),
+            (0b11, _) => self.word_set(addr, 0xffff),
+            (_, 0b11) => self.wen = true,
+            (_, 0b00) => self.wen = false,
+            (_, 0b10) => (0..EEP_SZ / 2).for_each(|a| self.word_set(a, 0xffff)),
+            _ => return EepSt::Write(None, 0, 0),
+        }
+        self.dout = true;
+        EepSt::Idle
+    }
+}
+
+pub struct MBC7 {
+    sav_name: String,
+    rom: Vec<u8>,
+    rom_nb: usize,
+    ram_en: (bool, bool),
+    eep: Eep,
+    acc: (u16, u16),
+    lat: bool,
+    tilt: (i16, i16),
+}
+
+impl Drop for MBC7 {
+    fn drop(&mut self) {
+        save(&self.sav_name, &self.eep.data);
+    }
+}
+
+impl MBC7 {
+    fn with_rom(sav_name: String, rom: Vec<u8>) -> Box<MBC7> {
+        let mut data = vec![];
+
+        if load(Path::new(&sav_name), &mut data).is_none() {
+            data = vec![0xff; EEP_SZ];
+        }
+        data.resize(EEP_SZ, 0xff);
+        Box::new(MBC7 {
+            sav_name,
+            rom,
+            rom_nb: 0x01,
+            ram_en: (false, false),
+            eep: Eep::new(data),
+            acc: (0x8000, 0x8000),
+            lat: false,
+            tilt: (0, 0),
+        })
+    }
+}
+
+impl MBC for MBC7 {
+    fn new(path: &Path) -> Box<Self> {
+        MBC7::with_rom(sav_name(path), read_rom(path))
+    }
+
+    fn get(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4000..=0x7fff => self
+                .rom
+                .get(self.rom_bank(addr) * 0x4000 + addr as usize - 0x4000)
+                .cloned(),
+            0xa000..=0xafff if self.ram_en == (true, true) => Some(match addr >> 4 & 0xf {
+                0x2 => self.acc.0 as u8,
+                0x3 => (self.acc.0 >> 8) as u8,
+                0x4 => self.acc.1 as u8,
+                0x5 => (self.acc.1 >> 8) as u8,
+                0x6 => 0x00,
+                0x8 => self.eep.get(),
+                _ => 0xff,
+            }),
+            0xa000..=0xbfff => Some(0xff),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self, addr: u16, val: u8) -> Option<()> {
+        match addr {
+            0x0000..=0x1fff => {
+                self.ram_en.0 = val & 0xf == 0xa;
+            }
+            0x2000..=0x3fff => {
+                self.rom_nb = val as usize & 0x7f;
+            }
+            0x4000..=0x5fff => {
+                self.ram_en.1 = val == 0x40;
+            }
+            0x6000..=0x7fff => (),
+            0xa000..=0xafff if self.ram_en == (true, true) => match (addr >> 4 & 0xf, val) {
+                (0x0, 0x55) => {
+                    self.acc = (0x8000, 0x8000);
+                    self.lat = false;
+                }
+                (0x1, 0xaa) if !self.lat => {
+                    self.acc = (
+                        ACC_MID.wrapping_add(self.tilt.0 as u16),
+                        ACC_MID.wrapping_add(self.tilt.1 as u16),
+                    );
+                    self.lat = true;
+                }
+                (0x8, _) => self.eep.set(val),
+                _ => (),
+            },
+            0xa000..=0xbfff => (),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn rom(&self) -> Option<&[u8]> {
+        Some(&self.rom)
+    }
+
+    fn tilt(&mut self, x: i16, y: i16) {
+        self.tilt = (x, y);
+    }
+
+    fn snap(&self) -> Vec<u8> {
+        let mut result = vec![
+            self.rom_nb as u8,
+            self.ram_en.0 as u8,
+            self.ram_en.1 as u8,
+            self.lat as u8,
+            self.eep.wen as u8,
+        ];
+
+        result.extend_from_slice(&self.acc.0.to_le_bytes());
+        result.extend_from_slice(&self.acc.1.to_le_bytes());
+        result.extend_from_slice(&self.eep.data);
+        result
+    }
+
+    fn restore(&mut self, snap: &[u8]) {
+        self.rom_nb = snap[0] as usize;
+        self.ram_en = (snap[1] != 0, snap[2] != 0);
+        self.lat = snap[3] != 0;
+        self.acc = (
+            u16::from_le_bytes([snap[5], snap[6]]),
+            u16::from_le_bytes([snap[7], snap[8]]),
+        );
+        self.eep = Eep::new(snap[9..].to_vec());
+        self.eep.wen = snap[4] != 0;
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        if addr < 0x4000 {
+            0
+        } else {
+            self.rom_nb % (self.rom.len() / 0x4000).max(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtc() {
+        let mut rtc = Rtc::new(1000);
+
+        rtc.set(RamClk::H, 23, 1000);
+        rtc.set(RamClk::M, 59, 1000);
+        rtc.set(RamClk::DL, 0xff, 1000);
+        rtc.set(RamClk::DH, 0x01, 1000);
+        rtc.latch(1061);
+        assert_eq!(rtc.lat, [1, 0, 0, 0, 0x80]);
+        rtc.set(RamClk::DH, 0x40, 1061);
+        rtc.latch(5000);
+        assert_eq!(rtc.lat, [1, 0, 0, 0, 0x40]);
+        rtc.set(RamClk::S, 0x7f, 5000);
+        assert_eq!(rtc.regs[0], 0x3f);
+
+        let ftr = rtc.footer();
+
+        assert_eq!(ftr.len(), RTC_SZ);
+        assert_eq!(&ftr[16..20], &[0x40, 0, 0, 0]);
+        assert_eq!(
+            i64::from_le_bytes([
+                ftr[40], ftr[41], ftr[42], ftr[43], ftr[44], ftr[45], ftr[46], ftr[47]
+            ]),
+            5000
+        );
+
+        let copy = Rtc::parse(&ftr).unwrap();
+
+        assert_eq!((copy.regs, copy.lat, copy.base), (rtc.regs, rtc.lat, 5000));
+        assert_eq!(Rtc::parse(&ftr[..44]).map(|r| r.base), Some(5000));
+        assert!(Rtc::parse(&ftr[..40]).is_none());
+    }
+
+    fn rom(sz: usize, logos: &[usize]) -> Vec<u8> {
+        let mut result: Vec<u8> = (0..sz).map(|i| (i / 0x4000) as u8).collect();
+
+        result[0x148] = 0x05;
+        result[0x149] = 0x00;
+        for i in logos {
+            result[i * 0x40000 + 0x104..i * 0x40000 + 0x134].copy_from_slice(&NIN_LOGO);
+        }
+        result
+    }
+
+    #[test]
+    fn mbc1() {
+        let mut mbc = MBC1::with_rom(String::new(), rom(0x100000, &[0]));
+
+        assert!(!mbc.multi);
+        mbc.set(0x2000, 0x00);
+        assert_eq!(mbc.get(0x4000), Some(0x01));
+        mbc.set(0x2000, 0x12);
+        mbc.set(0x4000, 0x01);
+        assert_eq!(mbc.get(0x4000), Some(0x32));
+        assert_eq!(mbc.get(0x0000), None);
+        mbc.set(0x6000, 0x01);
+        assert_eq!(mbc.get(0x0000), Some(0x20));
+        mbc.set(0x4000, 0x03);
+        assert_eq!(mbc.rom_bank(0x4000), 0x32);
+
+        let mut mbc = MBC1::with_rom(String::new(), rom(0x80000, &[0]));
+
+        mbc.set(0x2000, 0x1f);
+        mbc.set(0x4000, 0x01);
+        assert_eq!(mbc.get(0x7fff), Some(0x1f));
+        assert_eq!(mbc.get(0xa000), None);
+        assert_eq!(mbc.set(0xa000, 0x12), None);
+    }
+
+    #[test]
+    fn mbc1m() {
+        let mut mbc = MBC1::with_rom(String::new(), rom(0x100000, &[0, 1, 2, 3]));
+
+        assert!(mbc.multi);
+        assert!(MBC1::is_multi(&rom(0x100000, &[0, 1, 2])));
+        assert!(!MBC1::is_multi(&rom(0x100000, &[0, 1])));
+        assert!(!MBC1::is_multi(&rom(0x80000, &[0, 1])));
+        mbc.set(0x2000, 0x12);
+        assert_eq!(mbc.get(0x4000), Some(0x02));
+        mbc.set(0x4000, 0x02);
+        assert_eq!(mbc.get(0x4000), Some(0x22));
+        mbc.set(0x2000, 0x10);
+        assert_eq!(mbc.get(0x4000), Some(0x20));
+        mbc.set(0x6000, 0x01);
+        assert_eq!(mbc.get(0x0100), Some(0x20));
+        mbc.set(0x4000, 0x03);
+        assert_eq!(mbc.rom_bank(0x0000), 0x30);
+        assert_eq!(mbc.rom_bank(0x4000), 0x30);
+    }
+
+    #[test]
+    fn mbc5() {
+        let mut rom = rom(0x8000, &[]);
+
+        rom[0x147] = 0x1e;
+        rom[0x149] = 0x02;
+        let mut mbc = MBC5::with_rom(String::new(), rom);
+
+        assert_eq!(mbc.rumble(), Some(false));
+        mbc.set(0x2000, 0xff);
+        mbc.set(0x3000, 0x01);
+        assert_eq!(mbc.get(0x4000), Some(0x01));
+        mbc.set(0x0000, 0x0a);
+        mbc.set(0x4000, 0x0f);
+        assert_eq!(mbc.rumble(), Some(true));
+        mbc.set(0xa000, 0x12);
+        assert_eq!(mbc.get(0xa000), Some(0x12));
+
+        let snap = mbc.snap();
+
+        mbc.set(0x4000, 0x00);
+        assert_eq!(mbc.rumble(), Some(false));
+        mbc.restore(&snap);
+        assert_eq!(mbc.rumble(), Some(true));
+        assert_eq!(mbc.rom_bank(0x4000), 0x01);
+    }
+
+    #[test]
+    fn rom_ram() {
+        let mut hdr = vec![0; 0x150];
+
+        hdr[0x149] = 0x01;
+        let mut mbc = RomRam::with_rom(String::new(), &hdr);
+
+        assert_eq!(mbc.ram.len(), 0x800);
+        assert_eq!(mbc.set(0x2000, 0x01), Some(()));
+        mbc.set(0xa001, 0x42);
+        assert_eq!(mbc.get(0xa801), Some(0x42));
+        assert_eq!(mbc.get(0x4000), None);
+        hdr[0x149] = 0x00;
+        assert_eq!(RomRam::with_rom(String::new(), &hdr).get(0xa000), None);
+    }
+
+    #[test]
+    fn mmm01() {
+        let mut rom: Vec<u8> = (0..0x20000).map(|i| (i / 0x4000) as u8).collect();
+
+        rom[0x18000 + 0x149] = 0x03;
+        rom[0x18000 + 0x147] = 0x0d;
+        let path = std::env::temp_dir().join("gbmu_mmm01_test.gb");
+
+        std::fs::write(&path, &rom).unwrap();
+        assert!(is_mmm01(&path));
+        std::fs::write(&path, &rom[..0x8000]).unwrap();
+        assert!(!is_mmm01(&path));
+
+        let mut short = vec![0; 0x150];
+
+        short[0x147] = 0x0b;
+        std::fs::write(&path, &short).unwrap();
+        let mbc = MMM01::new(&path);
+
+        assert_eq!(mbc.sav_name, "");
+        assert_eq!((mbc.get(0x0147), mbc.get(0x4000)), (Some(0x0b), Some(0xff)));
+        std::fs::write(&path, &short[..0x100]).unwrap();
+        assert_eq!(MMM01::new(&path).get(0x0000), Some(0x00));
+        std::fs::remove_file(path).unwrap();
+        let mut mbc = MMM01::with_rom(String::new(), rom);
+
+        assert_eq!((mbc.get(0x0000), mbc.get(0x4000)), (Some(6), Some(7)));
+        mbc.set(0x2000, 0x02);
+        mbc.set(0x6000, 0x3c);
+        assert_eq!(mbc.get(0x4000), Some(7));
+        mbc.set(0x0000, 0x40);
+        assert!(mbc.lock);
+        assert_eq!((mbc.get(0x0000), mbc.get(0x4000)), (Some(2), Some(3)));
+        mbc.set(0x2000, 0x00);
+        assert_eq!(mbc.get(0x4000), Some(3));
+        mbc.set(0x2000, 0x05);
+        assert_eq!(mbc.rom_bank(0x4000), 3);
+        mbc.set(0x0000, 0x0a);
+        assert!(mbc.lock && mbc.ram_en);
+        mbc.set(0x6000, 0x00);
+        assert_eq!(mbc.rom_msk, 0xf);
+        mbc.set(0xa000, 0x99);
+        assert_eq!(mbc.get(0xa000), Some(0x99));
+    }
+
+    #[test]
+    fn huc1() {
+        let mut rom: Vec<u8> = (0..0x20000).map(|i| (i / 0x4000) as u8).collect();
+
+        rom[0x149] = 0x03;
+        let mut mbc = HuC1::with_rom(String::new(), rom);
+
+        mbc.set(0x2000, 0x05);
+        assert_eq!(mbc.get(0x4000), Some(0x05));
+        mbc.set(0x2000, 0x00);
+        assert_eq!(mbc.get(0x4000), Some(0x01));
+        mbc.set(0x0000, 0x0a);
+        mbc.set(0x4000, 0x02);
+        mbc.set(0xa010, 0x5a);
+        assert_eq!(mbc.ram[0x4010], 0x5a);
+        mbc.set(0x0000, 0x0e);
+        assert_eq!(mbc.get(0xa010), Some(0xc0));
+        mbc.set(0xa000, 0x01);
+        assert!(mbc.ir_on);
+        mbc.light = true;
+        assert_eq!(mbc.get(0xa000), Some(0xc1));
+        mbc.set(0x0000, 0x00);
+        assert_eq!(mbc.get(0xa010), Some(0x5a));
+    }
+
+    #[test]
+    fn huc3() {
+        let mut rom = vec![0; 0x8000];
+
+        rom[0x149] = 0x02;
+        let mut mbc = HuC3::with_rom(String::new(), rom, 0);
+
+        for val in [0x40, 0x50, 0x3b, 0x35, 0x30, 0x32, 0x30, 0x30] {
+            mbc.exec(val, 0);
+        }
+        mbc.exec(0x61, 0);
+        assert_eq!((mbc.mins, mbc.days), (0x05b, 0x002));
+        mbc.exec(0x60, 1440 * 60 + 90);
+        assert_eq!(
+            (mbc.mins, mbc.days, mbc.base),
+            (0x05c, 0x003, 1440 * 60 + 60)
+        );
+        mbc.exec(0x40, 0);
+        mbc.exec(0x10, 0);
+        mbc.set(0x0000, 0x0c);
+        assert_eq!(mbc.get(0xa000), Some(0x9c));
+        mbc.set(0x0000, 0x0d);
+        assert_eq!(mbc.get(0xa000), Some(0x01));
+        mbc.set(0x0000, 0x0a);
+        mbc.set(0xa000, 0x77);
+        assert_eq!(mbc.get(0xa000), Some(0x77));
+        assert_eq!(mbc.footer().len(), HUC3_SZ);
+
+        let snap = mbc.snap();
+
+        mbc.tick(CLK_HZ * (1440 * 60 + 180) + 1);
+        mbc.set(0x0000, 0x0b);
+        mbc.set(0xa000, 0x60);
+        assert_eq!((mbc.mins, mbc.clk.secs), (0x05e, 1440 * 60 + 180));
+        mbc.restore(&snap);
+        assert_eq!((mbc.mins, mbc.clk.secs, mbc.clk.cyc), (0x05c, 0, 0));
+    }
+
+    fn eep_cmd(eep: &mut Eep, bits: &[u8]) -> Vec<u8> {
+        let mut result = vec![];
+
+        eep.set(0x00);
+        eep.set(0x80);
+        for b in [1].iter().chain(bits) {
+            eep.set(0x80 | b << 1);
+            eep.set(0xc0 | b << 1);
+            result.push(eep.get() & 0x1);
+        }
+        result
+    }
+
+    fn eep_bits(val: u16, n: usize) -> Vec<u8> {
+        (0..n).rev().map(|i| (val >> i) as u8 & 0x1).collect()
+    }
+
+    #[test]
+    fn mbc7() {
+        let mut mbc = MBC7::with_rom(String::new(), vec![0; 0x8000]);
+
+        assert_eq!(mbc.get(0xa020), Some(0xff));
+        mbc.set(0x0000, 0x0a);
+        mbc.set(0x4000, 0x40);
+        assert_eq!((mbc.get(0xa020), mbc.get(0xa030)), (Some(0x00), Some(0x80)));
+        mbc.tilt(-0x70, 0x70);
+        mbc.set(0xa000, 0x55);
+        mbc.set(0xa010, 0xaa);
+        mbc.tilt(0, 0);
+        mbc.set(0xa010, 0xaa);
+        assert_eq!((mbc.get(0xa020), mbc.get(0xa030)), (Some(0x60), Some(0x81)));
+        assert_eq!((mbc.get(0xa040), mbc.get(0xa050)), (Some(0x40), Some(0x82)));
+        assert_eq!((mbc.get(0xa060), mbc.get(0xa070)), (Some(0x00), Some(0xff)));
+
+        let eep = &mut mbc.eep;
+
+        eep_cmd(eep, &eep_bits(0b01_00000101, 10));
+        eep_cmd(
+            eep,
+            &[&eep_bits(0b01_00000101, 10)[..], &eep_bits(0x1234, 16)].concat(),
+        );
+        assert_eq!(eep.word(5), 0xffff);
+        eep_cmd(eep, &eep_bits(0b00_11000000, 10));
+        eep_cmd(
+            eep,
+            &[&eep_bits(0b01_00000101, 10)[..], &eep_bits(0x1234, 16)].concat(),
+        );
+        assert_eq!(eep.word(5), 0x1234);
+        assert_eq!(eep.data[10..12], [0x34, 0x12]);
+
+        let out = eep_cmd(eep, &[&eep_bits(0b10_00000101, 10)[..], &[0; 16]].concat());
+
+        assert_eq!(out[10], 0);
+        assert_eq!(out[11..], eep_bits(0x1234, 16)[..]);
+        eep_cmd(eep, &eep_bits(0b11_00000101, 10));
+        assert_eq!(eep.word(5), 0xffff);
+        eep_cmd(
+            eep,
+            &[&eep_bits(0b00_01000000, 10)[..], &eep_bits(0xabcd, 16)].concat(),
+        );
+        assert_eq!((eep.word(0), eep.word(127)), (0xabcd, 0xabcd));
+        eep_cmd(eep, &eep_bits(0b00_10000000, 10));
+        assert_eq!(eep.word(64), 0xffff);
+        eep_cmd(eep, &eep_bits(0b00_00000000, 10));
+        assert!(!eep.wen);
+    }
 }
@@ -1,14 +1,19 @@
+use crate::cdl::*;
+use crate::cheat::*;
 use crate::input::*;
 use crate::mbc::*;
 use crate::reg::api::*;
 use crate::utils::*;
+use minifb::Key;
 use parking_lot::RwLock;
+use std::cell::RefCell;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::sync::Arc;
 
 pub const MEM_SZ: usize = 0x10000;
+const CALLS_SZ: usize = 0x400;
 
 pub type SM = Arc<RwLock<SndMem>>;
 
@@ -28,10 +33,60 @@ impl SndMem {
     }
 }
 
+impl Default for SndMem {
+    fn default() -> SndMem {
+        SndMem::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Wtch {
+    pub start: u16,
+    pub end: u16,
+    pub rd: bool,
+    pub wr: bool,
+}
+
+pub struct WtchHit {
+    pub idx: usize,
+    pub addr: u16,
+    pub old: u8,
+    pub new: Option<u8>,
+}
+
+#[derive(Clone)]
+pub struct Frame {
+    pub ret: u16,
+    pub ret_bank: usize,
+    pub to: u16,
+    pub to_bank: usize,
+    pub sp: u16,
+    pub int: bool,
+}
+
+pub struct MemSnap {
+    data: Vec<u8>,
+    snd: Vec<u8>,
+    mbc: Vec<u8>,
+    keys: Option<Vec<Key>>,
+    pub keys_n: u64,
+    calls: Vec<Frame>,
+    calls_bad: usize,
+}
+
 pub struct Mem {
     pub data: Vec<u8>,
     pub snd_data: SM,
     pub inputs: Inputs,
+    pub wtchs: Vec<Wtch>,
+    pub wtch_hits: RefCell<Vec<WtchHit>>,
+    pub calls: Vec<Frame>,
+    pub calls_bad: usize,
+    pub int_evt: Option<usize>,
+    pub rmbl_evt: Option<bool>,
+    pub chts: Cheats,
+    pub cdl: Cdl,
+    pub boot: bool,
     mbc: Box<dyn MBC>,
 }
 
@@ -41,6 +96,15 @@ impl Mem {
             data: vec![0; MEM_SZ],
             snd_data: Arc::new(RwLock::new(SndMem::new())),
             inputs: Inputs::new(),
+            wtchs: Vec::new(),
+            wtch_hits: RefCell::new(Vec::new()),
+            calls: Vec::new(),
+            calls_bad: 0,
+            int_evt: None,
+            rmbl_evt: None,
+            chts: Cheats::new(),
+            cdl: Cdl::new(0),
+            boot: false,
             mbc: MBC0::new(Path::new("")),
         };
         if path != "" {
@@ -50,13 +114,24 @@ impl Mem {
             if let Err(_) = result.load_rom(0x100, Path::new("../roms/DMG_ROM.gb")) {
                 fatal_err("Can't load bootrom", 11);
             }
+            result.boot = true;
             result.mbc = match result.data[0x147] {
+                _ if is_mmm01(Path::new(path)) => MMM01::new(Path::new(path)),
                 0x01 | 0x02 | 0x03 => MBC1::new(Path::new(path)),
                 0x05 | 0x06 => MBC2::new(Path::new(path)),
+                0x08 | 0x09 => RomRam::new(Path::new(path)),
+                0x0b..=0x0d => MMM01::new(Path::new(path)),
                 0x0f | 0x10 | 0x11 | 0x12 | 0x13 => MBC3::new(Path::new(path)),
                 0x19 | 0x1a | 0x1b | 0x1c | 0x1d | 0x1e => MBC5::new(Path::new(path)),
+                0x22 => MBC7::new(Path::new(path)),
+                0xfe => HuC3::new(Path::new(path)),
+                0xff => HuC1::new(Path::new(path)),
                 _ => MBC0::new(Path::new(path)),
             };
+            result.cdl = Cdl::for_rom(
+                Path::new(path),
+                result.mbc.rom().map_or(0x8000, |rom| rom.len()),
+            );
         }
         result
     }
@@ -77,8 +152,39 @@ impl Mem {
         self.get(addr, true)
     }
 
+    fn wtch(&self, addr: u16, old: u8, new: Option<u8>) {
+        for (idx, w) in self.wtchs.iter().enumerate() {
+            if addr >= w.start && addr <= w.end && if new.is_some() { w.wr } else { w.rd } {
+                self.wtch_hits.borrow_mut().push(WtchHit {
+                    idx,
+                    addr,
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+
     pub fn get(&self, addr: u16, su: bool) -> u8 {
-        if let Some(res) = self.mbc.get(addr) {
+        let result = if addr < 0x8000 {
+            if !su && !self.boot {
+                self.cdl.mark(self.rom_off(addr), CDL_DATA);
+            }
+            self.chts.patch(addr, self.rd(addr, su))
+        } else {
+            self.rd(addr, su)
+        };
+
+        if !su && !self.wtchs.is_empty() {
+            self.wtch(addr, result, None);
+        }
+        result
+    }
+
+    fn rd(&self, addr: u16, su: bool) -> u8 {
+        if self.boot && addr < 0x100 {
+            self.data[addr as usize]
+        } else if let Some(res) = self.mbc.get(addr) {
             res
         } else if addr >= 0xff10 && addr <= 0xff3f {
             self.snd_data.read().get(addr)
@@ -97,6 +203,166 @@ impl Mem {
         }
     }
 
+    pub fn cheat_frame(&mut self) {
+        for (bank, addr, val) in self.chts.shark() {
+            if (0xa000..=0xbfff).contains(&addr) && bank & 0xf0 == 0x80 {
+                self.mbc.ram_set(bank as usize & 0xf, addr, val);
+            } else {
+                self.su_set(addr, val);
+            }
+        }
+    }
+
+    pub fn snap(&self) -> MemSnap {
+        MemSnap {
+            data: self.data.clone(),
+            snd: self.snd_data.read().0.clone(),
+            mbc: self.mbc.snap(),
+            keys: self.inputs.keys.clone(),
+            keys_n: self.inputs.n,
+            calls: self.calls.clone(),
+            calls_bad: self.calls_bad,
+        }
+    }
+
+    pub fn restore(&mut self, snap: &MemSnap) -> Result<(), String> {
+        if snap.data.len() != self.data.len()
+            || snap.snd.len() != self.snd_data.read().0.len()
+            || snap.mbc.len() != self.mbc.snap().len()
+        {
+            return Err(String::from("Snapshot doesn't match the loaded cartridge"));
+        }
+        self.data.copy_from_slice(&snap.data);
+        self.snd_data.write().0.copy_from_slice(&snap.snd);
+        self.mbc.restore(&snap.mbc);
+        self.inputs.keys = snap.keys.clone();
+        self.inputs.n = snap.keys_n;
+        self.calls = snap.calls.clone();
+        self.calls_bad = snap.calls_bad;
+        self.wtch_hits.borrow_mut().clear();
+        self.int_evt = None;
+        self.rmbl_evt = None;
+        Ok(())
+    }
+
+    pub fn push_call(&mut self, ret: u16, to: u16, sp: u16) {
+        if self.calls.len() == CALLS_SZ {
+            self.calls.remove(0);
+        }
+        self.calls.push(Frame {
+            ret,
+            ret_bank: self.rom_bank(ret),
+            to,
+            to_bank: self.rom_bank(to),
+            sp,
+            int: false,
+        });
+    }
+
+    pub fn pop_call(&mut self, sp: u16) {
+        let len = self.calls.len();
+
+        while self.calls.last().is_some_and(|f| f.sp < sp) {
+            self.calls.pop();
+        }
+        if self.calls.last().is_some_and(|f| f.sp == sp) {
+            self.calls.pop();
+            if self.calls.len() + 1 != len {
+                self.calls_bad += 1;
+            }
+        } else {
+            self.calls_bad += 1;
+        }
+    }
+
+    pub fn rom_bank(&self, addr: u16) -> usize {
+        self.mbc.rom_bank(addr)
+    }
+
+    pub fn tilt(&mut self, (x, y): (i16, i16)) {
+        self.mbc.tilt(x, y);
+    }
+
+    pub fn tick(&mut self, cycles: usize) {
+        self.mbc.tick(cycles);
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.mbc.rumble() == Some(true)
+    }
+
+    pub fn rom_off(&self, addr: u16) -> usize {
+        self.rom_bank(addr) * 0x4000 + (addr as usize & 0x3fff)
+    }
+
+    pub fn cdl_exec(&self, addr: u16, len: usize) {
+        for i in 0..len as u16 {
+            let addr = addr.wrapping_add(i);
+
+            if addr < 0x8000 {
+                self.cdl
+                    .mark(self.rom_off(addr), if i == 0 { CDL_CODE } else { CDL_OPER });
+            }
+        }
+    }
+
+    pub fn cdl_get(&self, addr: u16) -> u8 {
+        if addr < 0x8000 {
+            self.cdl.get(self.rom_off(addr))
+        } else {
+            0
+        }
+    }
+
+    pub fn rom_get(&self, bank: usize, addr: u16) -> u8 {
+        match self.mbc.rom() {
+            Some(rom) => rom
+                .get(bank * 0x4000 + (addr as usize & 0x3fff))
+                .cloned()
+                .unwrap_or(0xff),
+            None => self.data[(addr as usize & 0x3fff) | if bank == 0 { 0 } else { 0x4000 }],
+        }
+    }
+
+    pub fn bank_get(&self, bank: Option<usize>, addr: u16) -> Option<u8> {
+        match (bank, addr) {
+            (None, _) => Some(self.su_get(addr)),
+            (Some(bank), 0x0000..=0x7fff) => match self.mbc.rom() {
+                Some(rom) => rom.get(bank * 0x4000 + (addr as usize & 0x3fff)).cloned(),
+                None => {
+                    (bank < 2 && (bank == 0) == (addr < 0x4000)).then(|| self.data[addr as usize])
+                }
+            },
+            (Some(bank), 0xa000..=0xbfff) => self.mbc.ram_get(bank, addr),
+            (Some(0), 0x8000..=0x9fff | 0xc000..=0xcfff) | (Some(1), 0xd000..=0xdfff) => {
+                Some(self.su_get(addr))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn bank_set(&mut self, bank: Option<usize>, addr: u16, val: u8) -> Result<(), &str> {
+        match (bank, addr) {
+            (_, 0x0000..=0x7fff) if bank.is_some() => Err("ROM is read-only"),
+            (None, _) => {
+                self.su_set(addr, val);
+                Ok(())
+            }
+            (Some(bank), 0xa000..=0xbfff) => match self.mbc.ram_get(bank, addr) {
+                Some(_) => {
+                    self.mbc.ram_set(bank, addr, val);
+                    Ok(())
+                }
+                None => Err("Wrong bank"),
+            },
+            (Some(0), 0x8000..=0x9fff | 0xc000..=0xcfff) | (Some(1), 0xd000..=0xdfff) => {
+                self.su_set(addr, val);
+                Ok(())
+            }
+            _ => Err("Wrong bank"),
+        }
+    }
+
     pub fn nu_set(&mut self, addr: u16, val: u8) {
         self.set(addr, val, false);
     }
@@ -106,9 +372,25 @@ impl Mem {
     }
 
     pub fn set(&mut self, addr: u16, val: u8, su: bool) {
+        if !su && !self.wtchs.is_empty() {
+            let old = self.su_get(addr);
+
+            self.wr(addr, val, su);
+            self.wtch(addr, old, Some(self.su_get(addr)));
+        } else {
+            self.wr(addr, val, su);
+        }
+    }
+
+    fn wr(&mut self, addr: u16, val: u8, su: bool) {
         let mut tmp = val;
 
+        let rmbl = self.mbc.rumble();
+
         if let Some(_) = self.mbc.set(addr, val) {
+            if self.mbc.rumble() != rmbl {
+                self.rmbl_evt = self.mbc.rumble();
+            }
         } else if addr >= 0xff10 && addr <= 0xff3f {
             self.snd_data.write().set(addr, tmp);
         } else {
@@ -220,4 +502,86 @@ mod tests {
         assert_eq!(first_bytes, mem.data[..12]);
         assert_eq!(mem.data[12..15], [0, 0, 0]);
     }
+
+    #[test]
+    fn banks() {
+        let mut mem = Mem::new("");
+
+        mem.su_set(0x4100, 0x12);
+        mem.su_set(0x8000, 0x34);
+        assert_eq!(mem.bank_get(Some(1), 0x4100), Some(0x12));
+        assert_eq!(mem.bank_get(Some(0), 0x4100), None);
+        assert_eq!(mem.bank_get(Some(2), 0x4100), None);
+        assert_eq!(mem.bank_get(Some(0), 0x8000), Some(0x34));
+        assert_eq!(mem.bank_get(Some(1), 0x8000), None);
+        assert_eq!(mem.bank_get(Some(0), 0xa000), None);
+        assert_eq!(mem.bank_get(None, 0xff80), Some(0));
+        assert_eq!(mem.bank_set(Some(1), 0x4100, 0), Err("ROM is read-only"));
+        assert_eq!(mem.bank_set(Some(1), 0xd000, 0x56), Ok(()));
+        assert_eq!(mem.bank_get(None, 0xd000), Some(0x56));
+        assert_eq!(mem.bank_set(Some(2), 0xd000, 0x56), Err("Wrong bank"));
+    }
+
+    #[test]
+    fn snap() {
+        let mut mem = Mem::new("");
+
+        mem.su_set(0xc000, 0x11);
+        mem.su_set(NR10, 0x22);
+        mem.push_call(0x0153, 0x2000, 0xfffc);
+        let snap = mem.snap();
+
+        mem.su_set(0xc000, 0x33);
+        mem.su_set(NR10, 0x44);
+        mem.pop_call(0xfffc);
+        assert_eq!(mem.restore(&snap), Ok(()));
+        assert_eq!(mem.su_get(0xc000), 0x11);
+        assert_eq!(mem.su_get(NR10), 0x22);
+        assert_eq!(mem.calls.len(), 1);
+
+        let mut snap = mem.snap();
+
+        snap.data.pop();
+        assert!(mem.restore(&snap).is_err());
+    }
+
+    #[test]
+    fn watch() {
+        let mut mem = Mem::new("");
+
+        mem.wtchs.push(Wtch {
+            start: 0xc000,
+            end: 0xc00f,
+            rd: false,
+            wr: true,
+        });
+        mem.wtchs.push(Wtch {
+            start: 0xc008,
+            end: 0xc008,
+            rd: true,
+            wr: true,
+        });
+        mem.su_set(0xc000, 0x11);
+        mem.nu_get(0xc000);
+        mem.nu_set(0xc010, 0x22);
+        assert!(mem.wtch_hits.borrow().is_empty());
+        mem.nu_set(0xc000, 0x33);
+        mem.nu_get(0xc008);
+        mem.nu_set(0xc008, 0x44);
+        let hits: Vec<(usize, u16, u8, Option<u8>)> = mem
+            .wtch_hits
+            .borrow()
+            .iter()
+            .map(|h| (h.idx, h.addr, h.old, h.new))
+            .collect();
+        assert_eq!(
+            hits,
+            vec![
+                (0, 0xc000, 0x11, Some(0x33)),
+                (1, 0xc008, 0x00, None),
+                (0, 0xc008, 0x00, Some(0x44)),
+                (1, 0xc008, 0x00, Some(0x44)),
+            ]
+        );
+    }
 }
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+
+pub struct Tbl {
+    chrs: HashMap<u8, String>,
+}
+
+impl Tbl {
+    pub fn parse(txt: &str) -> Tbl {
+        let mut chrs = HashMap::new();
+
+        for line in txt.lines() {
+            let line = line.trim_end_matches(['\r', '\n']);
+            let (hex, chr) = match line.split_once('=') {
+                Some(e) => e,
+                None => continue,
+            };
+
+            if let (Ok(b), false) = (u8::from_str_radix(hex.trim(), 16), chr.is_empty()) {
+                chrs.insert(b, String::from(chr));
+            }
+        }
+        Tbl { chrs }
+    }
+
+    pub fn load(path: &str) -> Result<Tbl, String> {
+        let txt = read_to_string(path).map_err(|_| format!("Can't read {}", path))?;
+        let result = Tbl::parse(&txt);
+
+        if result.chrs.is_empty() {
+            Err(format!("No entries in {}", path))
+        } else {
+            Ok(result)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chrs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chrs.is_empty()
+    }
+
+    fn chr(&self, b: u8) -> char {
+        self.chrs
+            .get(&b)
+            .and_then(|s| s.chars().next())
+            .unwrap_or('.')
+    }
+
+    pub fn enc(&self, txt: &str) -> Option<Vec<u8>> {
+        let mut result = Vec::new();
+        let mut rest = txt;
+
+        while !rest.is_empty() {
+            let (b, s) = self
+                .chrs
+                .iter()
+                .filter(|(_, s)| rest.starts_with(s.as_str()))
+                .max_by_key(|(b, s)| (s.len(), std::cmp::Reverse(**b)))?;
+
+            result.push(*b);
+            rest = &rest[s.len()..];
+        }
+        Some(result)
+    }
+}
+
+fn chr(b: u8, tbl: Option<&Tbl>) -> char {
+    match tbl {
+        Some(tbl) => tbl.chr(b),
+        None if (0x20..0x7f).contains(&b) => b as char,
+        None => '.',
+    }
+}
+
+pub fn dump(addr: u16, bytes: &[Option<u8>], bank: Option<usize>, tbl: Option<&Tbl>) -> String {
+    let mut result = String::new();
+
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let addr = addr.wrapping_add(i as u16 * 16);
+        let hex: Vec<String> = line
+            .iter()
+            .map(|b| b.map_or(String::from("--"), |b| format!("{:02x}", b)))
+            .collect();
+        let txt: String = line
+            .iter()
+            .map(|b| b.map_or(' ', |b| chr(b, tbl)))
+            .collect();
+
+        result.push_str(&match bank {
+            Some(bank) => format!("{:02x}:{:04x}: ", bank, addr),
+            None => format!("0x{:04x}: ", addr),
+        });
+        result.push_str(&format!("{:<48}|{}|\n", hex.join(" "), txt));
+    }
+    result
+}
+
+pub fn pat(s: &str, tbl: Option<&Tbl>) -> Result<Vec<u8>, String> {
+    if let Some(txt) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        let result = match tbl {
+            Some(tbl) => tbl
+                .enc(txt)
+                .ok_or_else(|| String::from("Text not in charmap"))?,
+            None => txt.bytes().collect(),
+        };
+
+        if result.is_empty() {
+            Err(String::from("Empty pattern"))
+        } else {
+            Ok(result)
+        }
+    } else {
+        s.split_whitespace()
+            .map(|b| {
+                u8::from_str_radix(b.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("Bad byte: {}", b))
+            })
+            .collect()
+    }
+}
+
+pub fn find(data: &[Option<u8>], pat: &[u8]) -> Vec<usize> {
+    if pat.is_empty() {
+        return vec![];
+    }
+    data.windows(pat.len())
+        .enumerate()
+        .filter(|(_, w)| w.iter().zip(pat).all(|(b, p)| *b == Some(*p)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_find() {
+        let tbl = Tbl::parse("80=A\n81=B\n82=C\nA0=th\nFF= \n");
+        let bytes: Vec<Option<u8>> = [0x80, 0x81, 0x41, 0x00, 0xa0]
+            .iter()
+            .map(|b| Some(*b))
+            .chain([None])
+            .collect();
+
+        assert_eq!(tbl.len(), 5);
+        assert_eq!(
+            dump(0xc000, &bytes, None, None),
+            format!("0xc000: {:<48}|..A.. |\n", "80 81 41 00 a0 --")
+        );
+        assert_eq!(
+            dump(0x4000, &bytes, Some(2), Some(&tbl)),
+            format!("02:4000: {:<48}|AB..t |\n", "80 81 41 00 a0 --")
+        );
+        assert_eq!(tbl.enc("ABth C"), Some(vec![0x80, 0x81, 0xa0, 0xff, 0x82]));
+        assert_eq!(tbl.enc("x"), None);
+        assert_eq!(pat("\"AB\"", Some(&tbl)), Ok(vec![0x80, 0x81]));
+        assert_eq!(pat("\"AB\"", None), Ok(vec![0x41, 0x42]));
+        assert_eq!(pat("41 0x00 a0", None), Ok(vec![0x41, 0x00, 0xa0]));
+        assert!(pat("4g", None).is_err());
+        assert_eq!(find(&bytes, &[0x81, 0x41]), vec![1]);
+        assert_eq!(find(&bytes, &[0xa0, 0x00]), Vec::<usize>::new());
+    }
+}
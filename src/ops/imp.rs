@@ -337,6 +337,7 @@ pub fn jr_cc_sn(pc: MRR, cc: bool, sn: i8) -> bool {
 pub fn call_cc_nn(m: MMy, sp: MRR, pc: MRR, cc: bool, nn: u16) -> bool {
     if cc {
         push_arr_rr(m, sp, pc, false);
+        m.push_call(grr(pc), nn, grr(sp));
         srr(pc, nn);
         return true;
     }
@@ -345,6 +346,7 @@ pub fn call_cc_nn(m: MMy, sp: MRR, pc: MRR, cc: bool, nn: u16) -> bool {
 
 pub fn ret_cc(m: MMy, pc: MRR, sp: MRR, cc: bool) -> bool {
     if cc {
+        m.pop_call(grr(sp));
         pop_rr_arr(m, pc, sp);
         return true;
     }
@@ -352,6 +354,7 @@ pub fn ret_cc(m: MMy, pc: MRR, sp: MRR, cc: bool) -> bool {
 }
 
 pub fn reti(m: MMy, ime: MRR, pc: MRR, sp: MRR) -> bool {
+    m.pop_call(grr(sp));
     pop_rr_arr(m, pc, sp);
     srr(ime, 1);
     true
@@ -359,6 +362,7 @@ pub fn reti(m: MMy, ime: MRR, pc: MRR, sp: MRR) -> bool {
 
 pub fn rst(m: MMy, sp: MRR, pc: MRR, nn: u16) -> bool {
     push_arr_rr(m, sp, pc, false);
+    m.push_call(grr(pc), nn, grr(sp));
     srr(pc, nn);
     true
 }
@@ -248,6 +248,36 @@ fn jp_call() {
     assert_eq!(grr(&ime), 1);
 }
 
+#[test]
+fn call_stack() {
+    let mut mem = Mem::new("");
+    let mut sp = Reg::new();
+    let mut pc = Reg::new();
+    let mut ime = Reg::new();
+
+    ld_rr_nn(&mut sp, 0xfffe);
+    ld_rr_nn(&mut pc, 0x0153);
+    call_cc_nn(&mut mem, &mut sp, &mut pc, true, 0x2000);
+    rst(&mut mem, &mut sp, &mut pc, 0x38);
+    call_cc_nn(&mut mem, &mut sp, &mut pc, false, 0x3000);
+    assert_eq!(mem.calls.len(), 2);
+    assert_eq!(mem.calls[0].ret, 0x0153);
+    assert_eq!(mem.calls[0].to, 0x2000);
+    assert_eq!(mem.calls[1].ret, 0x2000);
+    assert_eq!(mem.calls[1].sp, 0xfffa);
+    reti(&mut mem, &mut ime, &mut pc, &mut sp);
+    ret_cc(&mut mem, &mut pc, &mut sp, false);
+    assert_eq!(mem.calls.len(), 1);
+    assert_eq!(mem.calls_bad, 0);
+    call_cc_nn(&mut mem, &mut sp, &mut pc, true, 0x3000);
+    ld_rr_nn(&mut sp, 0xfffc);
+    ret_cc(&mut mem, &mut pc, &mut sp, true);
+    assert_eq!(mem.calls.len(), 0);
+    assert_eq!(mem.calls_bad, 1);
+    ret_cc(&mut mem, &mut pc, &mut sp, true);
+    assert_eq!(mem.calls_bad, 2);
+}
+
 #[test]
 fn arit_16() {
     let mut af = Reg::new();
@@ -29,6 +29,10 @@ impl Op {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn exec(&self, r: &mut Regs, m: &mut Mem, p: u16) -> bool {
         (self.func)(r, m, p)
     }
@@ -1663,6 +1667,12 @@ impl Ops {
     }
 }
 
+impl Default for Ops {
+    fn default() -> Ops {
+        Ops::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
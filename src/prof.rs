@@ -0,0 +1,219 @@
+use crate::ioreg::*;
+use crate::mem::*;
+use crate::sym::*;
+use std::collections::HashMap;
+use std::fs::write;
+
+pub struct Prof {
+    pub on: bool,
+    total: u64,
+    halt: u64,
+    ints: [u64; 5],
+    pcs: HashMap<(usize, u16), u64>,
+    fns: HashMap<(usize, u16), u64>,
+    stks: HashMap<(Vec<(usize, u16)>, bool), u64>,
+    lbls: Vec<(usize, u16)>,
+}
+
+fn region(addr: u16) -> u8 {
+    match addr {
+        0x0000..=0x3fff => 0,
+        0x4000..=0x7fff => 1,
+        _ => 2,
+    }
+}
+
+fn pct(n: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.
+    } else {
+        n as f64 * 100. / total as f64
+    }
+}
+
+impl Default for Prof {
+    fn default() -> Prof {
+        Prof::new()
+    }
+}
+
+impl Prof {
+    pub fn new() -> Prof {
+        Prof {
+            on: false,
+            total: 0,
+            halt: 0,
+            ints: [0; 5],
+            pcs: HashMap::new(),
+            fns: HashMap::new(),
+            stks: HashMap::new(),
+            lbls: Vec::new(),
+        }
+    }
+
+    pub fn start(&mut self, syms: &Syms) {
+        *self = Prof::new();
+        self.lbls = syms
+            .iter()
+            .filter(|(_, _, nm)| !nm.contains('.'))
+            .map(|(bank, addr, _)| (bank, addr))
+            .collect();
+        self.lbls.sort_unstable();
+        self.lbls.dedup();
+        self.on = true;
+    }
+
+    fn func(&self, bank: usize, addr: u16) -> Option<(usize, u16)> {
+        let idx = self.lbls.partition_point(|l| *l <= (bank, addr));
+
+        self.lbls[..idx]
+            .last()
+            .filter(|l| l.0 == bank && region(l.1) == region(addr))
+            .cloned()
+    }
+
+    pub fn add(&mut self, m: &Mem, loc: (usize, u16), halt: bool, cycles: usize) {
+        let cycles = cycles as u64;
+        let root = m.calls.first().map_or(loc, |f| (f.ret_bank, f.ret));
+        let mut stk: Vec<(usize, u16)> = vec![self.func(root.0, root.1).unwrap_or((0, 0x100))];
+
+        stk.extend(m.calls.iter().map(|f| (f.to_bank, f.to)));
+        self.total += cycles;
+        if let Some(f) = m.calls.iter().rev().find(|f| f.int) {
+            if (0x40..=0x60).contains(&f.to) {
+                self.ints[(f.to as usize - 0x40) / 8] += cycles;
+            }
+        }
+        if halt {
+            self.halt += cycles;
+        } else {
+            *self.pcs.entry(loc).or_default() += cycles;
+            if let Some(f) = self.func(loc.0, loc.1) {
+                *self.fns.entry(f).or_default() += cycles;
+            }
+        }
+        *self.stks.entry((stk, halt)).or_default() += cycles;
+    }
+
+    fn name(&self, syms: &Syms, bank: usize, addr: u16) -> String {
+        match syms.get(bank, addr) {
+            Some(nm) => String::from(nm),
+            None if (0x40..=0x60).contains(&addr) && addr.is_multiple_of(8) => {
+                format!("int_{}", INTS[(addr as usize - 0x40) / 8].to_lowercase())
+            }
+            None => format!("{:02x}:{:04x}", bank, addr),
+        }
+    }
+
+    pub fn report(&self, syms: &Syms, max: usize) -> String {
+        let mut result = format!("Total: {} cycles\n", self.total);
+        let line = |n: u64, nm: &str| format!("{:>12} {:>6.2}%  {}\n", n, pct(n, self.total), nm);
+
+        result.push_str(&line(self.halt, "HALT"));
+        result.push_str("Interrupts:\n");
+        for (i, n) in self.ints.iter().enumerate().filter(|(_, n)| **n != 0) {
+            result.push_str(&line(*n, INTS[i]));
+        }
+        if !self.fns.is_empty() {
+            let mut fns: Vec<(&(usize, u16), &u64)> = self.fns.iter().collect();
+
+            fns.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            result.push_str("Functions:\n");
+            for ((bank, addr), n) in fns.iter().take(max) {
+                result.push_str(&line(**n, &self.name(syms, *bank, *addr)));
+            }
+        }
+        let mut pcs: Vec<(&(usize, u16), &u64)> = self.pcs.iter().collect();
+
+        pcs.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        result.push_str("Addresses:\n");
+        for ((bank, addr), n) in pcs.iter().take(max) {
+            let nm = match self.func(*bank, *addr) {
+                Some((fb, fa)) => format!(
+                    "{:02x}:{:04x} ({}+0x{:x})",
+                    bank,
+                    addr,
+                    self.name(syms, fb, fa),
+                    addr - fa
+                ),
+                None => format!("{:02x}:{:04x}", bank, addr),
+            };
+
+            result.push_str(&line(**n, &nm));
+        }
+        result
+    }
+
+    pub fn folded(&self, syms: &Syms) -> String {
+        let mut lines: Vec<String> = self
+            .stks
+            .iter()
+            .map(|((stk, halt), n)| {
+                let mut nms: Vec<String> = stk
+                    .iter()
+                    .map(|(bank, addr)| self.name(syms, *bank, *addr))
+                    .collect();
+
+                if *halt {
+                    nms.push(String::from("HALT"));
+                }
+                format!("{} {}", nms.join(";"), n)
+            })
+            .collect();
+
+        lines.sort();
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+
+    pub fn save(&self, syms: &Syms, path: &str) -> Result<usize, String> {
+        write(path, self.folded(syms)).map_err(|_| format!("Can't write {}", path))?;
+        Ok(self.stks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles() {
+        let syms =
+            Syms::parse("00:0150 Start\n00:0158 Start.loop\n01:4000 Update\n00:0040 VBlank\n");
+        let mut mem = Mem::new("");
+        let mut prof = Prof::new();
+
+        prof.start(&syms);
+        prof.add(&mem, (0, 0x0158), false, 8);
+        mem.calls.push(Frame {
+            ret: 0x015b,
+            ret_bank: 0,
+            to: 0x4000,
+            to_bank: 1,
+            sp: 0xfffc,
+            int: false,
+        });
+        prof.add(&mem, (1, 0x4002), false, 12);
+        mem.calls.push(Frame {
+            ret: 0x4005,
+            ret_bank: 1,
+            to: 0x0040,
+            to_bank: 0,
+            sp: 0xfffa,
+            int: true,
+        });
+        prof.add(&mem, (0, 0x0040), false, 20);
+        mem.calls.clear();
+        prof.add(&mem, (0, 0x015c), true, 4);
+        assert_eq!(prof.total, 44);
+        assert_eq!(prof.halt, 4);
+        assert_eq!(prof.ints, [20, 0, 0, 0, 0]);
+        assert_eq!(prof.fns.get(&(0, 0x0150)), Some(&8));
+        assert_eq!(prof.fns.get(&(1, 0x4000)), Some(&12));
+        assert_eq!(prof.func(1, 0x0200), None);
+        assert_eq!(
+            prof.folded(&syms),
+            "Start 8\nStart;HALT 4\nStart;Update 12\nStart;Update;VBlank 20\n"
+        );
+        assert!(prof.report(&syms, 10).contains("Start+0x8"));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::mem::*;
 use std::fmt;
 
+#[derive(Clone)]
 pub struct Reg {
     val: u16,
 }
@@ -48,6 +49,13 @@ impl Reg {
     }
 }
 
+impl Default for Reg {
+    fn default() -> Reg {
+        Reg::new()
+    }
+}
+
+#[derive(Clone)]
 pub struct Regs {
     pub af: Reg,
     pub bc: Reg,
@@ -121,6 +129,12 @@ impl Regs {
     }
 }
 
+impl Default for Regs {
+    fn default() -> Regs {
+        Regs::new()
+    }
+}
+
 impl fmt::Display for Regs {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -194,8 +208,82 @@ pub mod api {
     pub const OBP1: u16 = 0xff49;
     pub const WY: u16 = 0xff4a;
     pub const WX: u16 = 0xff4b;
+    pub const KEY1: u16 = 0xff4d;
+    pub const VBK: u16 = 0xff4f;
+    pub const BOOT: u16 = 0xff50;
+    pub const HDMA1: u16 = 0xff51;
+    pub const HDMA2: u16 = 0xff52;
+    pub const HDMA3: u16 = 0xff53;
+    pub const HDMA4: u16 = 0xff54;
+    pub const HDMA5: u16 = 0xff55;
+    pub const RP: u16 = 0xff56;
+    pub const BCPS: u16 = 0xff68;
+    pub const BCPD: u16 = 0xff69;
+    pub const OCPS: u16 = 0xff6a;
+    pub const OCPD: u16 = 0xff6b;
+    pub const SVBK: u16 = 0xff70;
     pub const IE: u16 = 0xffff;
 
+    pub const IO_NMS: [(u16, &str); 57] = [
+        (P1, "P1"),
+        (SB, "SB"),
+        (SC, "SC"),
+        (DIV, "DIV"),
+        (TIMA, "TIMA"),
+        (TMA, "TMA"),
+        (TAC, "TAC"),
+        (IF, "IF"),
+        (NR10, "NR10"),
+        (NR11, "NR11"),
+        (NR12, "NR12"),
+        (NR13, "NR13"),
+        (NR14, "NR14"),
+        (NR21, "NR21"),
+        (NR22, "NR22"),
+        (NR23, "NR23"),
+        (NR24, "NR24"),
+        (NR30, "NR30"),
+        (NR31, "NR31"),
+        (NR32, "NR32"),
+        (NR33, "NR33"),
+        (NR34, "NR34"),
+        (NR41, "NR41"),
+        (NR42, "NR42"),
+        (NR43, "NR43"),
+        (NR44, "NR44"),
+        (NR50, "NR50"),
+        (NR51, "NR51"),
+        (NR52, "NR52"),
+        (WPRAM, "WPRAM"),
+        (LCDC, "LCDC"),
+        (STAT, "STAT"),
+        (SCY, "SCY"),
+        (SCX, "SCX"),
+        (LY, "LY"),
+        (LYC, "LYC"),
+        (DMA, "DMA"),
+        (BGP, "BGP"),
+        (OBP0, "OBP0"),
+        (OBP1, "OBP1"),
+        (WY, "WY"),
+        (WX, "WX"),
+        (KEY1, "KEY1"),
+        (VBK, "VBK"),
+        (BOOT, "BOOT"),
+        (HDMA1, "HDMA1"),
+        (HDMA2, "HDMA2"),
+        (HDMA3, "HDMA3"),
+        (HDMA4, "HDMA4"),
+        (HDMA5, "HDMA5"),
+        (RP, "RP"),
+        (BCPS, "BCPS"),
+        (BCPD, "BCPD"),
+        (OCPS, "OCPS"),
+        (OCPD, "OCPD"),
+        (SVBK, "SVBK"),
+        (IE, "IE"),
+    ];
+
     pub const U: bool = true;
     pub const D: bool = false;
 
@@ -206,6 +294,17 @@ pub mod api {
     pub type F<'a> = (&'a Reg, u16);
     pub type MF<'a> = (&'a mut Reg, u16);
 
+    pub fn io_nm(addr: u16) -> Option<&'static str> {
+        IO_NMS.iter().find(|e| e.0 == addr).map(|e| e.1)
+    }
+
+    pub fn io_addr(nm: &str) -> Option<u16> {
+        IO_NMS
+            .iter()
+            .find(|e| e.1.eq_ignore_ascii_case(nm))
+            .map(|e| e.0)
+    }
+
     pub fn grr(r: RR) -> u16 {
         r.get_16()
     }
@@ -0,0 +1,181 @@
+use crate::mem::*;
+
+const RANGES: [(u16, u16); 2] = [(0xc000, 0xdfff), (0xff80, 0xfffe)];
+const SRAM_BANKS: usize = 16;
+
+type Loc = (Option<usize>, u16);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SrchOp {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+}
+
+pub struct Search {
+    pub w16: bool,
+    cands: Vec<(Loc, u16)>,
+}
+
+impl Default for Search {
+    fn default() -> Search {
+        Search::new()
+    }
+}
+
+impl Search {
+    pub fn new() -> Search {
+        Search {
+            w16: false,
+            cands: Vec::new(),
+        }
+    }
+
+    fn val(m: My, (bank, addr): Loc, w16: bool) -> u16 {
+        let rd = |a: u16| m.bank_get(bank, a).unwrap_or(0xff) as u16;
+
+        if w16 {
+            rd(addr) | rd(addr + 1) << 8
+        } else {
+            rd(addr)
+        }
+    }
+
+    pub fn start(&mut self, m: My, w16: bool) {
+        let last = |end: u16| if w16 { end - 1 } else { end };
+        let sram = (0..SRAM_BANKS)
+            .take_while(|bank| m.bank_get(Some(*bank), 0xa000).is_some())
+            .flat_map(|bank| (0xa000..=last(0xbfff)).map(move |addr| (Some(bank), addr)));
+        let ram = RANGES
+            .iter()
+            .flat_map(|(start, end)| (*start..=last(*end)).map(|addr| (None, addr)));
+
+        self.w16 = w16;
+        self.cands = sram
+            .chain(ram)
+            .map(|loc| (loc, Search::val(m, loc, w16)))
+            .collect();
+    }
+
+    pub fn op(nm: &str) -> Option<SrchOp> {
+        match nm {
+            "eq" => Some(SrchOp::Eq),
+            "ne" => Some(SrchOp::Ne),
+            "gt" => Some(SrchOp::Gt),
+            "lt" => Some(SrchOp::Lt),
+            _ => None,
+        }
+    }
+
+    pub fn filter(&mut self, m: My, op: SrchOp, rhs: Option<u16>) {
+        let w16 = self.w16;
+
+        self.cands = self
+            .cands
+            .iter()
+            .map(|(loc, prev)| (*loc, *prev, Search::val(m, *loc, w16)))
+            .filter(|(_, prev, cur)| {
+                let rhs = rhs.unwrap_or(*prev);
+
+                match op {
+                    SrchOp::Eq => *cur == rhs,
+                    SrchOp::Ne => *cur != rhs,
+                    SrchOp::Gt => *cur > rhs,
+                    SrchOp::Lt => *cur < rhs,
+                }
+            })
+            .map(|(loc, _, cur)| (loc, cur))
+            .collect();
+    }
+
+    pub fn len(&self) -> usize {
+        self.cands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cands.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<(Loc, u16)> {
+        self.cands.get(idx).cloned()
+    }
+
+    pub fn cheat(&self, idx: usize, val: Option<u16>) -> Option<Vec<String>> {
+        let ((bank, addr), cur) = self.get(idx)?;
+        let val = val.unwrap_or(cur);
+        let typ = bank.map_or(0x01, |b| 0x80 | b);
+        let code = |addr: u16, val: u8| {
+            format!("{:02X}{:02X}{:02X}{:02X}", typ, val, addr as u8, addr >> 8)
+        };
+
+        Some(if self.w16 {
+            vec![code(addr, val as u8), code(addr + 1, (val >> 8) as u8)]
+        } else {
+            vec![code(addr, val as u8)]
+        })
+    }
+
+    pub fn to_str(&self, m: My, max: usize) -> String {
+        let mut result = format!(
+            "{} candidate{} ({}-bit)\n",
+            self.cands.len(),
+            if self.cands.len() == 1 { "" } else { "s" },
+            if self.w16 { 16 } else { 8 }
+        );
+
+        for (idx, (loc, prev)) in self.cands.iter().take(max).enumerate() {
+            let cur = Search::val(m, *loc, self.w16);
+            let addr = match loc {
+                (Some(bank), addr) => format!("{:02x}:{:04x}", bank, addr),
+                (None, addr) => format!("0x{:04x}", addr),
+            };
+
+            result.push_str(&if self.w16 {
+                format!("{}: {} = 0x{:04x} (was 0x{:04x})\n", idx, addr, cur, prev)
+            } else {
+                format!("{}: {} = 0x{:02x} (was 0x{:02x})\n", idx, addr, cur, prev)
+            });
+        }
+        if self.cands.len() > max {
+            result.push_str("...\n");
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let mut mem = Mem::new("");
+        let mut srch = Search::new();
+
+        mem.su_set(0xc010, 5);
+        mem.su_set(0xff90, 5);
+        srch.start(&mem, false);
+        assert_eq!(srch.len(), 0x2000 + 0x7f);
+        srch.filter(&mem, SrchOp::Eq, Some(5));
+        assert_eq!(srch.len(), 2);
+        mem.su_set(0xc010, 4);
+        mem.su_set(0xff90, 6);
+        srch.filter(&mem, SrchOp::Lt, None);
+        assert_eq!(srch.get(0), Some(((None, 0xc010), 4)));
+        assert_eq!(srch.len(), 1);
+        assert_eq!(
+            srch.cheat(0, Some(0x63)),
+            Some(vec![String::from("016310C0")])
+        );
+        srch.start(&mem, true);
+        mem.su_set(0xc011, 0x12);
+        srch.filter(&mem, SrchOp::Ne, None);
+        assert_eq!(srch.get(0), Some(((None, 0xc010), 0x1204)));
+        assert_eq!(srch.get(1), Some(((None, 0xc011), 0x0012)));
+        assert_eq!(
+            srch.cheat(0, None),
+            Some(vec![String::from("010410C0"), String::from("011211C0")])
+        );
+    }
+}
@@ -9,6 +9,8 @@ const SAMPLE_RATE: u32 = 96000;
 const SND_DIV: f32 = 6.;
 const FILT_SZ: usize = 4;
 
+pub struct AudioSnap(Oscillators);
+
 pub struct Audio {
     _stream: cpal::Stream,
     _sample_rate: u32,
@@ -65,6 +67,14 @@ impl Audio {
     pub fn update(&mut self) {
         self.oscs.lock().update();
     }
+
+    pub fn snap(&self) -> AudioSnap {
+        AudioSnap(self.oscs.lock().clone())
+    }
+
+    pub fn restore(&mut self, snap: &AudioSnap) {
+        *self.oscs.lock() = snap.0.clone();
+    }
 }
 
 fn stream_thrd(out_buff: &mut [f32], oscs: Arc<FairMutex<Oscillators>>) {
@@ -86,6 +96,7 @@ fn stream_thrd(out_buff: &mut [f32], oscs: Arc<FairMutex<Oscillators>>) {
 }
 
 //add osc trait to make an osc array
+#[derive(Clone)]
 struct Oscillators {
     osc1: Square,
     osc1_pan: (f32, f32),
@@ -189,6 +200,7 @@ impl Oscillators {
     }
 }
 
+#[derive(Clone)]
 struct Square {
     init: bool,
     freq: u16,
@@ -384,6 +396,7 @@ impl Square {
     }
 }
 
+#[derive(Clone)]
 struct Wave {
     init: bool,
     freq: u16,
@@ -488,6 +501,7 @@ impl Wave {
     }
 }
 
+#[derive(Clone)]
 struct Noise {
     init: bool,
     freq: f32,
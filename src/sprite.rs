@@ -1,6 +1,7 @@
 use crate::mem::*;
 use crate::reg::api::*;
 
+#[derive(Clone)]
 pub struct Sprite {
     pos: (isize, isize),
     tile: u16,
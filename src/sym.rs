@@ -0,0 +1,135 @@
+use std::collections::{hash_map::Entry, HashMap};
+use std::fs::read_to_string;
+use std::path::Path;
+
+pub struct Syms {
+    by_addr: HashMap<u16, Vec<(usize, String)>>,
+    by_nm: HashMap<String, (usize, u16)>,
+}
+
+impl Default for Syms {
+    fn default() -> Syms {
+        Syms::new()
+    }
+}
+
+impl Syms {
+    pub fn new() -> Syms {
+        Syms {
+            by_addr: HashMap::new(),
+            by_nm: HashMap::new(),
+        }
+    }
+
+    pub fn parse(txt: &str) -> Syms {
+        let mut result = Syms::new();
+
+        for line in txt.lines() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            let mut fields = line.split_whitespace();
+            let (loc, nm) = match (fields.next(), fields.next()) {
+                (Some(loc), Some(nm)) => (loc, nm),
+                _ => continue,
+            };
+            let mut loc = loc.split(':');
+            let (bank, addr) = match (loc.next(), loc.next()) {
+                (Some(bank), Some(addr)) => (
+                    usize::from_str_radix(bank, 16),
+                    u16::from_str_radix(addr, 16),
+                ),
+                _ => continue,
+            };
+
+            if let (Ok(bank), Ok(addr)) = (bank, addr) {
+                result
+                    .by_addr
+                    .entry(addr)
+                    .or_default()
+                    .push((bank, String::from(nm)));
+                match result.by_nm.entry(nm.to_lowercase()) {
+                    Entry::Occupied(_) => println!(
+                        "Warning: Symbol {} is ignored, names are not case sensitive",
+                        nm
+                    ),
+                    Entry::Vacant(e) => {
+                        e.insert((bank, addr));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub fn for_rom(rom: &str) -> Option<Syms> {
+        let rom = Path::new(rom);
+
+        for path in [
+            rom.with_extension("sym"),
+            Path::new(&format!("{}.sym", rom.display())).to_path_buf(),
+        ] {
+            if path.exists() {
+                return read_to_string(&path).ok().map(|s| Syms::parse(&s));
+            }
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_nm.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_nm.is_empty()
+    }
+
+    pub fn get(&self, bank: usize, addr: u16) -> Option<&str> {
+        let lbls = self.by_addr.get(&addr)?;
+
+        if addr < 0x8000 {
+            lbls.iter().find(|l| l.0 == bank).map(|l| &l.1[..])
+        } else {
+            lbls.first().map(|l| &l.1[..])
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u16, &str)> {
+        self.by_addr
+            .iter()
+            .flat_map(|(addr, lbls)| lbls.iter().map(move |l| (l.0, *addr, &l.1[..])))
+    }
+
+    pub fn find(&self, nm: &str) -> Option<(usize, u16)> {
+        self.by_nm.get(&nm.to_lowercase()).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let syms = Syms::parse(
+            "; File generated by rgblink\n\
+            00:0150 Start\n\
+            00:0150 Start.init ; local\n\
+            01:4000 MainLoop\n\
+            02:4000 Bank2Code\n\
+            00:c000 wPlayerX\n\
+            00:c001 WPLAYERX\n\
+            bad line\n\
+            zz:0000 Bad\n",
+        );
+
+        assert_eq!(syms.len(), 5);
+        assert_eq!(syms.get(0, 0x150), Some("Start"));
+        assert_eq!(syms.get(1, 0x4000), Some("MainLoop"));
+        assert_eq!(syms.get(2, 0x4000), Some("Bank2Code"));
+        assert_eq!(syms.get(3, 0x4000), None);
+        assert_eq!(syms.get(1, 0xc000), Some("wPlayerX"));
+        assert_eq!(syms.find("mainloop"), Some((1, 0x4000)));
+        assert_eq!(syms.find("Start.init"), Some((0, 0x150)));
+        assert_eq!(syms.find("Bad"), None);
+        assert_eq!(syms.find("WPLAYERX"), Some((0, 0xc000)));
+    }
+}
@@ -3,6 +3,7 @@ use crate::reg::api::*;
 
 const DIV_T: usize = 256;
 
+#[derive(Clone)]
 pub struct Timer {
     div_cy: usize,
     tima_cy: usize,
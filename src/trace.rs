@@ -0,0 +1,200 @@
+use crate::disasm::*;
+use crate::mem::*;
+use crate::reg::{api::*, *};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrcFmt {
+    Doctor,
+    Bgb,
+}
+
+pub struct Trace {
+    fmt: TrcFmt,
+    file: Option<BufWriter<File>>,
+    ring: VecDeque<String>,
+    ring_sz: usize,
+}
+
+impl Default for Trace {
+    fn default() -> Trace {
+        Trace::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace {
+            fmt: TrcFmt::Doctor,
+            file: None,
+            ring: VecDeque::new(),
+            ring_sz: 0,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.file.is_some() || self.ring_sz != 0
+    }
+
+    pub fn start_file(&mut self, path: &str, fmt: TrcFmt) -> Result<(), &str> {
+        self.stop();
+        self.file = Some(BufWriter::new(
+            File::create(path).map_err(|_| "Can't create trace file")?,
+        ));
+        self.fmt = fmt;
+        Ok(())
+    }
+
+    pub fn start_ring(&mut self, sz: usize, fmt: TrcFmt) {
+        self.stop();
+        self.ring.clear();
+        self.ring_sz = sz;
+        self.fmt = fmt;
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            if file.flush().is_err() {
+                println!("Error: Can't write to trace file");
+            }
+        }
+        self.ring_sz = 0;
+    }
+
+    pub fn dump(&self, path: &str) -> Result<usize, &str> {
+        let mut file = BufWriter::new(File::create(path).map_err(|_| "Can't create trace file")?);
+
+        for line in &self.ring {
+            writeln!(file, "{}", line).map_err(|_| "Can't write to trace file")?;
+        }
+        file.flush().map_err(|_| "Can't write to trace file")?;
+        Ok(self.ring.len())
+    }
+
+    pub fn status(&self) -> String {
+        if self.file.is_some() {
+            format!("Tracing to file ({:?})", self.fmt)
+        } else if self.ring_sz != 0 {
+            format!(
+                "Tracing to ring buffer ({}/{} lines, {:?})",
+                self.ring.len(),
+                self.ring_sz,
+                self.fmt
+            )
+        } else {
+            format!("Tracing off ({} lines buffered)", self.ring.len())
+        }
+    }
+
+    pub fn line(fmt: TrcFmt, m: My, r: &Regs, ins: &Ins, bank: usize) -> String {
+        let pc = grr(&r.pc);
+        let f = gr((&r.af, D));
+
+        match fmt {
+            TrcFmt::Doctor => format!(
+                "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} \
+                SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+                gr((&r.af, U)),
+                f,
+                gr((&r.bc, U)),
+                gr((&r.bc, D)),
+                gr((&r.de, U)),
+                gr((&r.de, D)),
+                gr((&r.hl, U)),
+                gr((&r.hl, D)),
+                grr(&r.sp),
+                pc,
+                m.su_get(pc),
+                m.su_get(pc.wrapping_add(1)),
+                m.su_get(pc.wrapping_add(2)),
+                m.su_get(pc.wrapping_add(3)),
+            ),
+            TrcFmt::Bgb => {
+                let bytes: Vec<String> = ins.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                let flgs: String = [(Z, 'Z'), (N, 'N'), (H, 'H'), (CY, 'C')]
+                    .iter()
+                    .map(|(mask, c)| if f as u16 & mask != 0 { *c } else { '-' })
+                    .collect();
+
+                format!(
+                    "A:{:02X} F:{} BC:{:04X} DE:{:04X} HL:{:04X} SP:{:04X} PC:{:04X} \
+                    |[{:02X}]0x{:04X}: {:<9} {}",
+                    gr((&r.af, U)),
+                    flgs,
+                    grr(&r.bc),
+                    grr(&r.de),
+                    grr(&r.hl),
+                    grr(&r.sp),
+                    pc,
+                    bank,
+                    pc,
+                    bytes.join(" "),
+                    ins.txt.to_lowercase()
+                )
+            }
+        }
+    }
+
+    pub fn log(&mut self, m: My, r: &Regs, ins: &Ins, bank: usize) {
+        let line = Trace::line(self.fmt, m, r, ins, bank);
+
+        if let Some(file) = &mut self.file {
+            if writeln!(file, "{}", line).is_err() {
+                println!("Error: Can't write to trace file");
+                self.file = None;
+            }
+        } else if self.ring_sz != 0 {
+            if self.ring.len() == self.ring_sz {
+                self.ring.pop_front();
+            }
+            self.ring.push_back(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line() {
+        let mut mem = Mem::new("");
+        let mut regs = Regs::new();
+
+        srr(&mut regs.pc, 0xc000);
+        mem.su_set(0xc000, 0xcd);
+        mem.su_set(0xc001, 0x34);
+        mem.su_set(0xc002, 0x12);
+        let ins = Disasm::new().ins(0xc000, &|a| mem.su_get(a));
+
+        assert_eq!(
+            Trace::line(TrcFmt::Doctor, &mem, &regs, &ins, 0),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C000 PCMEM:CD,34,12,00"
+        );
+        assert_eq!(
+            Trace::line(TrcFmt::Bgb, &mem, &regs, &ins, 0),
+            "A:01 F:Z-HC BC:0013 DE:00D8 HL:014D SP:FFFE PC:C000 |[00]0xC000: cd 34 12  call 0x1234"
+        );
+    }
+
+    #[test]
+    fn ring() {
+        let mem = Mem::new("");
+        let regs = Regs::new();
+        let ins = Disasm::new().ins(0, &|a| mem.su_get(a));
+        let mut trc = Trace::new();
+
+        assert!(!trc.is_on());
+        trc.start_ring(2, TrcFmt::Doctor);
+        for _ in 0..3 {
+            trc.log(&mem, &regs, &ins, 0);
+        }
+        assert!(trc.is_on());
+        assert_eq!(trc.ring.len(), 2);
+        trc.stop();
+        assert!(!trc.is_on());
+        assert_eq!(trc.ring.len(), 2);
+    }
+}